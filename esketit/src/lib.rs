@@ -0,0 +1,774 @@
+use platform::error::{self, Error};
+use platform::Money;
+
+pub mod secondary;
+pub mod sell;
+pub mod strategy;
+mod xsrf;
+
+const BASE_URL: &str = "https://esketit.com/api/investor";
+
+/// The platform's name in the ledger.
+pub const NAME: &str = "esketit";
+
+#[derive(serde::Deserialize)]
+pub struct Config {
+    pub username: String,
+    pub password: String,
+    pub max_term_period: u32,
+    pub min_interest_rate: f32,
+    #[serde(deserialize_with = "platform::deserialize_url")]
+    pub tfa_url: url::Url,
+    /// Signs the requests to the `tfa` server.
+    pub tfa_auth: Option<platform::TfaAuth>,
+    /// The client certificate for a `tfa` server behind mutual TLS.
+    pub tfa_tls: Option<platform::TfaTls>,
+    /// Where the Esketit API lives, only changed for testing.
+    #[serde(
+        default = "default_base_url",
+        deserialize_with = "platform::deserialize_url"
+    )]
+    pub base_url: url::Url,
+    /// Look at no more than this many loans, and as many secondary market
+    /// offers, all when unset.
+    pub max_loans: Option<usize>,
+    /// ISO codes of the currencies to invest in.
+    #[serde(default = "platform::default_currencies")]
+    pub currencies: Vec<String>,
+    #[serde(default)]
+    pub secondary_market: secondary::Rules,
+    #[serde(default)]
+    pub selling: sell::Rules,
+    #[serde(flatten)]
+    pub limits: platform::Limits,
+    #[serde(flatten)]
+    pub allocation: platform::Allocation,
+}
+
+fn default_base_url() -> url::Url {
+    url::Url::parse(BASE_URL).expect("BASE_URL is a valid URL")
+}
+
+#[derive(serde::Serialize)]
+struct LoginRequest {
+    email: String,
+    password: String,
+}
+
+#[derive(serde::Serialize, Debug)]
+struct TwoFactorAuthRequest {
+    totp: String,
+}
+
+#[derive(serde::Serialize)]
+struct AccountInfoRequest {
+    #[serde(rename = "currencyCode")]
+    currency_code: String,
+}
+
+#[derive(serde::Deserialize, Debug)]
+pub struct AccountInfoResponse {
+    #[serde(rename = "cashBalance")]
+    pub cash_balance: Money,
+    // ... other fields ...
+}
+
+#[derive(serde::Serialize)]
+struct PortfolioRequest {
+    page: u32,
+    #[serde(rename = "pageSize")]
+    page_size: u32,
+    filter: PortfolioFilter,
+}
+
+impl Paged for PortfolioRequest {
+    fn set_page(&mut self, page: u32) {
+        self.page = page;
+    }
+}
+
+#[derive(serde::Serialize)]
+struct PortfolioFilter {
+    #[serde(rename = "showActive")]
+    show_active: bool,
+    #[serde(rename = "showClosed")]
+    show_closed: bool,
+    #[serde(rename = "currencyCode")]
+    currency_code: String,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+pub struct CurrentInvestment {
+    #[serde(rename = "investmentId")]
+    pub investment_id: u64,
+    #[serde(rename = "loanId")]
+    pub loan_id: u64,
+    #[serde(rename = "interestRatePercent")]
+    pub interest_rate_percent: f64,
+    #[serde(rename = "investmentDate")]
+    pub investment_date: String,
+    #[serde(rename = "issueDate")]
+    pub issue_date: String,
+    #[serde(rename = "maturityDate")]
+    pub maturity_date: String,
+    #[serde(rename = "nextPaymentDate")]
+    pub next_payment_date: String,
+    #[serde(rename = "termInDays")]
+    pub term_in_days: i32,
+    #[serde(rename = "totalPayments")]
+    pub total_payments: u32,
+    #[serde(rename = "openPayments")]
+    pub open_payments: u32,
+    #[serde(rename = "closedPayments")]
+    pub closed_payments: u32,
+    #[serde(rename = "originatorCompanyName")]
+    pub originator_company_name: String,
+    #[serde(rename = "originatorId")]
+    pub originator_id: u64,
+    #[serde(rename = "productCode")]
+    pub product_code: String,
+    #[serde(rename = "productLabel")]
+    pub product_label: String,
+    #[serde(rename = "countryCode")]
+    pub country_code: String,
+    #[serde(rename = "collectionStatus")]
+    pub collection_status: String,
+    pub closed: bool,
+    #[serde(rename = "principalInvested")]
+    pub principal_invested: Money,
+    #[serde(rename = "principalOutstanding")]
+    pub principal_outstanding: Money,
+    #[serde(rename = "principalPaid")]
+    pub principal_paid: Money,
+    #[serde(rename = "principalPending")]
+    pub principal_pending: Money,
+    #[serde(rename = "principalReceived")]
+    pub principal_received: Money,
+    #[serde(rename = "interestPaid")]
+    pub interest_paid: Money,
+    #[serde(rename = "interestBonusPaid")]
+    pub interest_bonus_paid: Money,
+    #[serde(rename = "interestPending")]
+    pub interest_pending: Money,
+    #[serde(rename = "interestReceived")]
+    pub interest_received: Money,
+    #[serde(rename = "bonusPaid")]
+    pub bonus_paid: Money,
+    #[serde(rename = "bonusPending")]
+    pub bonus_pending: Money,
+    #[serde(rename = "bonusReceived")]
+    pub bonus_received: Money,
+    #[serde(rename = "totalPending")]
+    pub total_pending: Money,
+    #[serde(rename = "smOfferPrincipalAvailable")]
+    pub sm_offer_principal_available: Money,
+    #[serde(rename = "smPrincipalSold")]
+    pub sm_principal_sold: Money,
+    #[serde(rename = "smDiscountOrPremiumPercent")]
+    pub sm_discount_or_premium_percent: Option<f64>,
+    #[serde(rename = "currencyCode")]
+    pub currency_code: String,
+    #[serde(rename = "currencySymbol")]
+    pub currency_symbol: String,
+    #[serde(rename = "agreementFileName")]
+    pub agreement_file_name: String,
+    #[serde(rename = "agreementFileReference")]
+    pub agreement_file_reference: String,
+}
+
+/// Items asked for per page of a market query.
+const PAGE_SIZE: u32 = 20;
+
+/// A market query that can be asked for a specific page.
+trait Paged: serde::Serialize {
+    fn set_page(&mut self, page: u32);
+}
+
+#[derive(serde::Deserialize, Debug)]
+struct QueryResponse<T> {
+    items: Vec<T>,
+}
+
+#[derive(serde::Serialize)]
+struct QueryLoansRequest {
+    page: u32,
+    #[serde(rename = "pageSize")]
+    page_size: u32,
+    filter: LoansFilter,
+    #[serde(rename = "sortBy")]
+    sort_by: String,
+}
+
+impl Paged for QueryLoansRequest {
+    fn set_page(&mut self, page: u32) {
+        self.page = page;
+    }
+}
+
+#[derive(serde::Serialize)]
+struct LoansFilter {
+    #[serde(rename = "principalOfferFrom")]
+    principal_offer_from: String,
+    #[serde(rename = "currencyCode")]
+    currency_code: String,
+}
+
+#[derive(serde::Deserialize, serde::Serialize, Debug)]
+pub struct Loan {
+    #[serde(rename = "loanId")]
+    pub loan_id: i64,
+    #[serde(rename = "issueDate")]
+    pub issue_date: String,
+    #[serde(rename = "interestRatePercent")]
+    pub interest_rate_percent: f32,
+    #[serde(rename = "principalIssued")]
+    pub principal_issued: Money,
+    #[serde(rename = "principalOffer")]
+    pub principal_offer: Money,
+    #[serde(rename = "principalOutstanding")]
+    pub principal_outstanding: Money,
+    #[serde(rename = "currencyCode")]
+    pub currency_code: String,
+    #[serde(rename = "currencySymbol")]
+    pub currency_symbol: String,
+    #[serde(rename = "totalPayments")]
+    pub total_payments: i32,
+    #[serde(rename = "openPayments")]
+    pub open_payments: i32,
+    #[serde(rename = "closedPayments")]
+    pub closed_payments: i32,
+    #[serde(rename = "maturityDate")]
+    pub maturity_date: String,
+    #[serde(rename = "nextPaymentDate")]
+    pub next_payment_date: String,
+    #[serde(rename = "termInDays")]
+    pub term_in_days: i32,
+    #[serde(rename = "originatorCompanyName")]
+    pub originator_company_name: String,
+    #[serde(rename = "originatorId")]
+    pub originator_id: i64,
+    #[serde(rename = "productCode")]
+    pub product_code: String,
+    #[serde(rename = "productLabel")]
+    pub product_label: String,
+    #[serde(rename = "countryCode")]
+    pub country_code: String,
+    #[serde(rename = "hasBuyback")]
+    pub has_buyback: bool,
+    pub extensions: i32,
+    #[serde(rename = "extendedForDays")]
+    pub extended_for_days: i32,
+    #[serde(rename = "myInvestments")]
+    pub my_investments: Money,
+    #[serde(rename = "myInvestmentsPercent")]
+    pub my_investments_percent: f64,
+    #[serde(rename = "fundedPercent")]
+    pub funded_percent: i32,
+    #[serde(rename = "amountFunded")]
+    pub amount_funded: Money,
+    #[serde(rename = "amountAvailable")]
+    pub amount_available: Money,
+    #[serde(rename = "availablePercent")]
+    pub available_percent: i32,
+    #[serde(rename = "loanStatus")]
+    pub loan_status: String,
+}
+
+#[derive(serde::Serialize)]
+struct QueryInvestmentsRequest {
+    page: u32,
+    #[serde(rename = "pageSize")]
+    page_size: u32,
+    filter: InvestmentsFilter,
+    #[serde(rename = "sortBy")]
+    sort_by: String,
+}
+
+impl Paged for QueryInvestmentsRequest {
+    fn set_page(&mut self, page: u32) {
+        self.page = page;
+    }
+}
+
+#[derive(serde::Serialize)]
+struct InvestmentsFilter {
+    #[serde(rename = "currencyCode")]
+    currency_code: String,
+    #[serde(
+        rename = "smDiscountOrPremiumPercentFrom",
+        skip_serializing_if = "Option::is_none"
+    )]
+    sm_discount_or_premium_percent_from: Option<String>,
+    #[serde(
+        rename = "smDiscountOrPremiumPercentTo",
+        skip_serializing_if = "Option::is_none"
+    )]
+    sm_discount_or_premium_percent_to: Option<String>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+pub struct Investment {
+    #[serde(rename = "investmentId")]
+    pub investment_id: i64,
+    #[serde(rename = "loanId")]
+    pub loan_id: i64,
+    #[serde(rename = "issueDate")]
+    pub issue_date: String,
+    #[serde(rename = "interestRatePercent")]
+    pub interest_rate_percent: f32,
+    #[serde(rename = "currencyCode")]
+    pub currency_code: String,
+    #[serde(rename = "currencySymbol")]
+    pub currency_symbol: String,
+    #[serde(rename = "totalPayments")]
+    pub total_payments: i32,
+    #[serde(rename = "openPayments")]
+    pub open_payments: i32,
+    #[serde(rename = "closedPayments")]
+    pub closed_payments: i32,
+    #[serde(rename = "maturityDate")]
+    pub maturity_date: String,
+    #[serde(rename = "nextPaymentDate")]
+    pub next_payment_date: String,
+    #[serde(rename = "termInDays")]
+    pub term_in_days: i32,
+    #[serde(rename = "originatorCompanyName")]
+    pub originator_company_name: String,
+    #[serde(rename = "originatorId")]
+    pub originator_id: i64,
+    #[serde(rename = "productCode")]
+    pub product_code: String,
+    #[serde(rename = "productLabel")]
+    pub product_label: String,
+    #[serde(rename = "countryCode")]
+    pub country_code: String,
+    #[serde(rename = "collectionStatus")]
+    pub collection_status: String,
+    #[serde(rename = "smOfferPrincipalAvailable")]
+    pub sm_offer_principal_available: Money,
+    #[serde(rename = "smDiscountOrPremiumPercent")]
+    pub sm_discount_or_premium_percent: f64,
+    #[serde(rename = "smPrice")]
+    pub sm_price: Money,
+}
+
+#[derive(serde::Serialize)]
+struct InvestmentRequest {
+    #[serde(rename = "loanId")]
+    loan_id: u64,
+    amount: String,
+}
+
+#[derive(serde::Serialize)]
+struct BuyInvestmentRequest {
+    #[serde(rename = "investmentId")]
+    investment_id: u64,
+    amount: String,
+}
+
+#[derive(serde::Serialize)]
+struct SellInvestmentRequest {
+    #[serde(rename = "investmentId")]
+    investment_id: u64,
+    principal: String,
+    #[serde(rename = "discountOrPremiumPercent")]
+    discount_or_premium_percent: String,
+}
+
+#[derive(serde::Serialize)]
+struct CancelSaleRequest {
+    #[serde(rename = "investmentId")]
+    investment_id: u64,
+}
+
+impl From<&CurrentInvestment> for platform::Holding {
+    fn from(investment: &CurrentInvestment) -> Self {
+        platform::Holding {
+            loan_id: investment.loan_id as i64,
+            originator: investment.originator_id.to_string(),
+            country: investment.country_code.clone(),
+            amount: investment.principal_outstanding,
+        }
+    }
+}
+
+impl From<&Loan> for platform::Loan {
+    fn from(loan: &Loan) -> Self {
+        platform::Loan {
+            id: loan.loan_id,
+            interest_rate: loan.interest_rate_percent,
+            term: loan.term_in_days,
+            available_to_invest: loan.amount_available,
+            allowed_to_invest: loan.amount_available > Money::ZERO,
+            originator: loan.originator_id.to_string(),
+            country: loan.country_code.clone(),
+            currency: loan.currency_code.clone(),
+        }
+    }
+}
+
+/// Describes the loan behind a position, for the ledger.
+impl From<&CurrentInvestment> for platform::Loan {
+    fn from(investment: &CurrentInvestment) -> Self {
+        platform::Loan {
+            id: investment.loan_id as i64,
+            interest_rate: investment.interest_rate_percent as f32,
+            term: investment.term_in_days,
+            available_to_invest: Money::ZERO,
+            allowed_to_invest: false,
+            originator: investment.originator_id.to_string(),
+            country: investment.country_code.clone(),
+            currency: investment.currency_code.clone(),
+        }
+    }
+}
+
+/// Describes the loan behind a secondary market offer.
+impl From<&Investment> for platform::Loan {
+    fn from(investment: &Investment) -> Self {
+        platform::Loan {
+            id: investment.loan_id,
+            interest_rate: investment.interest_rate_percent,
+            term: investment.term_in_days,
+            available_to_invest: investment.sm_offer_principal_available,
+            allowed_to_invest: investment.sm_offer_principal_available > Money::ZERO,
+            originator: investment.originator_id.to_string(),
+            country: investment.country_code.clone(),
+            currency: investment.currency_code.clone(),
+        }
+    }
+}
+
+pub struct Client {
+    client: reqwest_middleware::ClientWithMiddleware,
+    config: Config,
+}
+
+/// Everything in one currency the strategy decides on.
+pub struct State {
+    pub currency: String,
+    pub portfolio: Vec<CurrentInvestment>,
+    pub available_loans: Vec<Loan>,
+    pub available_investments: Vec<Investment>,
+    pub cash_balance: Money,
+}
+
+impl Client {
+    pub fn new(config: Config) -> anyhow::Result<Client> {
+        let mut headers = reqwest::header::HeaderMap::new();
+        headers.insert(
+            reqwest::header::CONTENT_TYPE,
+            reqwest::header::HeaderValue::from_static("application/json"),
+        );
+        headers.insert(
+            reqwest::header::ACCEPT,
+            reqwest::header::HeaderValue::from_static("application/json"),
+        );
+
+        let client = reqwest::Client::builder()
+            .cookie_store(true)
+            .default_headers(headers)
+            .build()?;
+        let client = reqwest_middleware::ClientBuilder::new(client)
+            .with(xsrf::XsrfToken::default())
+            .build();
+
+        Ok(Client { client, config })
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// The XSRF token is taken care of by [`xsrf::XsrfToken`].
+    async fn send(
+        &mut self,
+        request: reqwest_middleware::RequestBuilder,
+    ) -> error::Result<reqwest::Response> {
+        error::check(request.send().await?).await
+    }
+
+    async fn post<B: serde::Serialize>(
+        &mut self,
+        path: &str,
+        body: &B,
+    ) -> error::Result<reqwest::Response> {
+        let request = self
+            .client
+            .post(platform::endpoint(&self.config.base_url, path))
+            .json(body);
+        self.send(request).await
+    }
+
+    /// Logs in with the password and a TOTP from the `tfa` server. This
+    /// sets the session cookies and the first XSRF token.
+    pub async fn login(&mut self) -> error::Result<()> {
+        let login_request = LoginRequest {
+            email: self.config.username.clone(),
+            password: self.config.password.clone(),
+        };
+        match self.post("/public/login", &login_request).await {
+            Err(Error::Api { status, body }) if status.is_client_error() => {
+                return Err(Error::Auth(body))
+            }
+            result => result?,
+        };
+
+        let totp = platform::fetch_totp(
+            self.config.tfa_url.clone(),
+            self.config.tfa_auth.as_ref(),
+            self.config.tfa_tls.as_ref(),
+        )
+        .await?;
+        let two_factor_auth_request = TwoFactorAuthRequest { totp };
+        match self
+            .post("/public/confirm-login", &two_factor_auth_request)
+            .await
+        {
+            Err(Error::Auth(body)) => return Err(Error::TwoFactor(body)),
+            Err(Error::Api { status, body }) if status.is_client_error() => {
+                return Err(Error::TwoFactor(body))
+            }
+            result => result?,
+        };
+
+        let request = self
+            .client
+            .get(platform::endpoint(&self.config.base_url, "/profile"));
+        self.send(request).await?;
+
+        Ok(())
+    }
+
+    pub async fn account_summary(&mut self, currency: &str) -> error::Result<AccountInfoResponse> {
+        let account_info_request = AccountInfoRequest {
+            currency_code: currency.to_string(),
+        };
+
+        let response = self.post("/account-summary", &account_info_request).await?;
+        error::json(response).await
+    }
+
+    /// Walks the pages of a query until a short page or `max_items` items.
+    async fn query_market<R: Paged, T: serde::de::DeserializeOwned>(
+        &mut self,
+        path: &str,
+        mut request: R,
+        max_items: Option<usize>,
+    ) -> error::Result<Vec<T>> {
+        let mut items = Vec::new();
+        for page in 1.. {
+            request.set_page(page);
+            let response = self.post(path, &request).await?;
+
+            let query_response: QueryResponse<T> = error::json(response).await?;
+            let last_page = query_response.items.len() < PAGE_SIZE as usize;
+            items.extend(query_response.items);
+            if last_page || max_items.is_some_and(|max| items.len() >= max) {
+                break;
+            }
+        }
+        if let Some(max) = max_items {
+            items.truncate(max);
+        }
+
+        Ok(items)
+    }
+
+    pub async fn primary_market(&mut self, currency: &str) -> error::Result<Vec<Loan>> {
+        let query_loans_request = QueryLoansRequest {
+            page: 1,
+            page_size: PAGE_SIZE,
+            sort_by: "interestRatePercent".to_string(),
+            filter: LoansFilter {
+                principal_offer_from: "5".to_string(),
+                currency_code: currency.to_string(),
+            },
+        };
+
+        let max_loans = self.config.max_loans;
+        self.query_market(
+            "/public/query-primary-market",
+            query_loans_request,
+            max_loans,
+        )
+        .await
+    }
+
+    /// The offers within the discount range of the secondary market rules.
+    pub async fn secondary_market(&mut self, currency: &str) -> error::Result<Vec<Investment>> {
+        let rules = &self.config.secondary_market;
+        let query_investments_request = QueryInvestmentsRequest {
+            page: 1,
+            page_size: PAGE_SIZE,
+            sort_by: "smDiscountOrPremiumPercent".to_string(),
+            filter: InvestmentsFilter {
+                currency_code: currency.to_string(),
+                sm_discount_or_premium_percent_from: Some(
+                    rules.discount_or_premium_from.to_string(),
+                ),
+                sm_discount_or_premium_percent_to: Some(rules.discount_or_premium_to.to_string()),
+            },
+        };
+
+        let max_loans = self.config.max_loans;
+        self.query_market(
+            "/public/query-secondary-market",
+            query_investments_request,
+            max_loans,
+        )
+        .await
+    }
+
+    /// All the active investments in the portfolio, `max_loans` doesn't
+    /// apply.
+    pub async fn portfolio(&mut self, currency: &str) -> error::Result<Vec<CurrentInvestment>> {
+        let request = PortfolioRequest {
+            page: 1,
+            page_size: PAGE_SIZE,
+            filter: PortfolioFilter {
+                show_active: true,
+                show_closed: false,
+                currency_code: currency.to_string(),
+            },
+        };
+
+        self.query_market("/query-my-investments", request, None)
+            .await
+    }
+
+    /// Logs in and fetches everything the strategy decides on, for each
+    /// currency in the config.
+    pub async fn fetch_remote_state(&mut self) -> anyhow::Result<Vec<State>> {
+        self.login().await?;
+
+        let mut states = Vec::new();
+        for currency in self.config.currencies.clone() {
+            states.push(self.fetch_currency_state(&currency).await?);
+        }
+        Ok(states)
+    }
+
+    async fn fetch_currency_state(&mut self, currency: &str) -> anyhow::Result<State> {
+        let account_info_response = self.account_summary(currency).await?;
+        println!(
+            "Cash balance: {} {}",
+            account_info_response.cash_balance, currency
+        );
+
+        let available_loans = self.primary_market(currency).await?;
+
+        println!(
+            "Found {} available loans on primary market",
+            available_loans.len()
+        );
+
+        let available_investments = self.secondary_market(currency).await?;
+
+        println!(
+            "Found {} available loans on secondary market",
+            available_investments.len()
+        );
+
+        let portfolio = self.portfolio(currency).await?;
+
+        println!("Current portfolio contains {} investments", portfolio.len());
+
+        Ok(State {
+            currency: currency.to_string(),
+            cash_balance: account_info_response.cash_balance,
+            portfolio,
+            available_investments,
+            available_loans,
+        })
+    }
+
+    pub async fn invest_loan(&mut self, loan_id: u64, amount: Money) -> error::Result<String> {
+        let investment_request = InvestmentRequest {
+            loan_id,
+            amount: amount.to_string(),
+        };
+
+        match self.post("/invest", &investment_request).await {
+            Ok(response) => Ok(response.text().await?),
+            Err(e) => Err(error::classify_investment_error(e)),
+        }
+    }
+
+    /// Buys (part of) an offer on the secondary market. `amount` is the
+    /// principal to buy, the price paid follows from the offer's discount.
+    pub async fn buy_investment(
+        &mut self,
+        investment_id: u64,
+        amount: Money,
+    ) -> error::Result<String> {
+        let buy_request = BuyInvestmentRequest {
+            investment_id,
+            amount: amount.to_string(),
+        };
+
+        match self.post("/buy-investment", &buy_request).await {
+            Ok(response) => Ok(response.text().await?),
+            Err(e) => Err(error::classify_investment_error(e)),
+        }
+    }
+
+    /// Offers `principal` of a position on the secondary market, at a
+    /// discount (negative) or premium (positive) in percent.
+    pub async fn sell_investment(
+        &mut self,
+        investment_id: u64,
+        principal: Money,
+        discount_or_premium: f64,
+    ) -> error::Result<String> {
+        let sell_request = SellInvestmentRequest {
+            investment_id,
+            principal: principal.to_string(),
+            discount_or_premium_percent: discount_or_premium.to_string(),
+        };
+
+        let response = self.post("/sell-investment", &sell_request).await?;
+        Ok(response.text().await?)
+    }
+
+    /// Takes a position's offer off the secondary market.
+    pub async fn cancel_sale(&mut self, investment_id: u64) -> error::Result<String> {
+        let cancel_request = CancelSaleRequest { investment_id };
+
+        let response = self.post("/cancel-sm-offer", &cancel_request).await?;
+        Ok(response.text().await?)
+    }
+}
+
+#[async_trait::async_trait]
+impl platform::Platform for Client {
+    fn name(&self) -> &str {
+        NAME
+    }
+
+    fn min_investment(&self) -> Money {
+        strategy::MIN_INVESTMENT
+    }
+
+    async fn login(&mut self) -> error::Result<()> {
+        Client::login(self).await
+    }
+
+    async fn balance(&mut self, currency: &str) -> error::Result<Money> {
+        Ok(self.account_summary(currency).await?.cash_balance)
+    }
+
+    async fn loans(&mut self, currency: &str) -> error::Result<Vec<platform::Loan>> {
+        let loans = self.primary_market(currency).await?;
+        Ok(loans.iter().map(platform::Loan::from).collect())
+    }
+
+    async fn holdings(&mut self, currency: &str) -> error::Result<Vec<platform::Holding>> {
+        let portfolio = self.portfolio(currency).await?;
+        Ok(portfolio.iter().map(platform::Holding::from).collect())
+    }
+
+    async fn invest(&mut self, loan: &platform::Loan, amount: Money) -> error::Result<String> {
+        self.invest_loan(loan.id as u64, amount).await
+    }
+}
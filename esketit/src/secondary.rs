@@ -0,0 +1,140 @@
+//! Rules for buying offers on the secondary market, set in the
+//! `[secondary_market]` table of the config.
+
+use crate::{Config, Investment};
+use chrono::NaiveDate;
+
+#[derive(serde::Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct Rules {
+    /// Buy offers at all.
+    pub buy: bool,
+    /// The range of discounts (negative) or premiums (positive) in percent
+    /// asked for from the market.
+    pub discount_or_premium_from: f64,
+    pub discount_or_premium_to: f64,
+    /// Days left until the maturity date, `max_term_period` when unset.
+    /// Offers past their maturity date are never bought.
+    pub max_remaining_days: Option<u32>,
+    /// Collection statuses an offer is bought in.
+    pub collection_statuses: Vec<String>,
+    /// `min_interest_rate` when unset.
+    pub min_interest_rate: Option<f32>,
+    /// The least [`yield_to_maturity`] in percent a year.
+    pub min_yield: Option<f64>,
+    /// Originators, by name or id, to buy from. All when empty.
+    pub originators: Vec<String>,
+    /// Originators, by name or id, never to buy from.
+    pub exclude_originators: Vec<String>,
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Rules {
+            buy: true,
+            discount_or_premium_from: -2.0,
+            discount_or_premium_to: -0.5,
+            max_remaining_days: None,
+            collection_statuses: vec!["CURRENT".to_string()],
+            min_interest_rate: None,
+            min_yield: None,
+            originators: Vec::new(),
+            exclude_originators: Vec::new(),
+        }
+    }
+}
+
+impl Rules {
+    /// The yield of the offer if it passes every rule, otherwise why not.
+    pub fn check(
+        &self,
+        investment: &Investment,
+        config: &Config,
+        today: NaiveDate,
+    ) -> Result<f64, String> {
+        let discount = investment.sm_discount_or_premium_percent;
+        if discount < self.discount_or_premium_from || discount > self.discount_or_premium_to {
+            return Err(format!("discount or premium of {}%", discount));
+        }
+        if !self
+            .collection_statuses
+            .contains(&investment.collection_status)
+        {
+            return Err(format!(
+                "collection status {}",
+                investment.collection_status
+            ));
+        }
+        let min_interest_rate = self.min_interest_rate.unwrap_or(config.min_interest_rate);
+        if investment.interest_rate_percent < min_interest_rate {
+            return Err(format!(
+                "interest rate of {}%",
+                investment.interest_rate_percent
+            ));
+        }
+        let days = remaining_days(&investment.maturity_date, today)
+            .ok_or_else(|| format!("maturity date {:?}", investment.maturity_date))?;
+        if days <= 0 {
+            return Err(format!("{} days overdue", -days));
+        }
+        let max_days = self.max_remaining_days.unwrap_or(config.max_term_period);
+        if days > max_days as i64 {
+            return Err(format!("{} days to maturity", days));
+        }
+
+        let originator = |name: &String| {
+            *name == investment.originator_company_name
+                || *name == investment.originator_id.to_string()
+        };
+        if !self.originators.is_empty() && !self.originators.iter().any(originator) {
+            return Err("originator not allowed".to_string());
+        }
+        if self.exclude_originators.iter().any(originator) {
+            return Err("originator excluded".to_string());
+        }
+
+        let ytm = yield_to_maturity(investment, today).ok_or("no price")?;
+        match self.min_yield {
+            Some(min_yield) if ytm < min_yield => Err(format!("yield of {:.2}%", ytm)),
+            _ => Ok(ytm),
+        }
+    }
+}
+
+/// Days from `today` until a loan's `maturity_date`, negative when it is
+/// overdue.
+pub fn remaining_days(maturity_date: &str, today: NaiveDate) -> Option<i64> {
+    let date = maturity_date.get(..10)?;
+    let maturity = NaiveDate::parse_from_str(date, "%Y-%m-%d").ok()?;
+    Some((maturity - today).num_days())
+}
+
+/// Estimates the yield in percent a year of buying the offer at `sm_price`
+/// and holding it to maturity: the principal and the interest until then,
+/// against the price paid. Ignores the payment schedule and the chance the
+/// loan is late. There's no yield to speak of for a loan that is due.
+pub fn yield_to_maturity(investment: &Investment, today: NaiveDate) -> Option<f64> {
+    let principal = investment.sm_offer_principal_available.to_f64();
+    let price = investment.sm_price.to_f64();
+    if principal <= 0.0 || price <= 0.0 {
+        return None;
+    }
+    let days = remaining_days(&investment.maturity_date, today)?;
+    if days <= 0 {
+        return None;
+    }
+    let days = days as f64;
+    let interest = investment.interest_rate_percent as f64 / 100.0 * days / 365.0;
+    let payback = principal * (1.0 + interest);
+    Some((payback / price - 1.0) * 365.0 / days * 100.0)
+}
+
+/// What buying a principal of one costs.
+pub fn price_ratio(investment: &Investment) -> f64 {
+    let principal = investment.sm_offer_principal_available.to_f64();
+    if principal > 0.0 {
+        investment.sm_price.to_f64() / principal
+    } else {
+        1.0
+    }
+}
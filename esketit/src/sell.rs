@@ -0,0 +1,168 @@
+//! Selling positions on the secondary market, set in the `[selling]` table
+//! of the config.
+
+use crate::{secondary, Client, CurrentInvestment};
+use chrono::NaiveDate;
+use platform::{Ledger, Money, Outcome};
+
+#[derive(serde::Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct Rules {
+    /// Put positions up for sale at all.
+    pub sell: bool,
+    /// Discount (negative) or premium (positive) in percent to ask.
+    pub discount_or_premium: f64,
+    /// Collection statuses a position is kept in, it's sold in any other.
+    pub keep_statuses: Vec<String>,
+    /// Sell positions with more days than this left until maturity.
+    pub max_remaining_days: Option<u32>,
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Rules {
+            sell: false,
+            discount_or_premium: 0.0,
+            keep_statuses: vec!["CURRENT".to_string()],
+            max_remaining_days: None,
+        }
+    }
+}
+
+impl Rules {
+    /// Why the position should be sold, if it should.
+    pub fn reason_to_sell(&self, position: &CurrentInvestment, today: NaiveDate) -> Option<String> {
+        if !self.keep_statuses.contains(&position.collection_status) {
+            return Some(format!("collection status {}", position.collection_status));
+        }
+        let days = secondary::remaining_days(&position.maturity_date, today)?;
+        match self.max_remaining_days {
+            Some(max) if days > max as i64 => Some(format!("{} days to maturity", days)),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Action {
+    /// Offer the principal at a discount or premium in percent.
+    List {
+        principal: Money,
+        discount_or_premium: f64,
+    },
+    /// Take the offer off the market.
+    Cancel,
+}
+
+#[derive(Debug, Clone)]
+pub struct Sale {
+    pub investment_id: u64,
+    pub action: Action,
+    pub reason: String,
+    /// The loan sold, for the ledger.
+    pub loan: platform::Loan,
+}
+
+/// Picks the positions to put up for sale: those in a collection status
+/// not in `keep_statuses` and those maturing later than
+/// `max_remaining_days`. Positions already on sale at another discount or
+/// premium than the configured one are stale, their offer is cancelled and
+/// listed again. Offers listed by an earlier run on positions no longer
+/// worth selling, say a loan back to current, are cancelled; offers made on
+/// the website are left alone. Nothing is sold unless `sell` is set.
+pub fn plan(
+    portfolio: &[CurrentInvestment],
+    rules: &Rules,
+    ledger: &Ledger,
+    today: NaiveDate,
+) -> anyhow::Result<Vec<Sale>> {
+    let mut sales = Vec::new();
+    if !rules.sell {
+        return Ok(sales);
+    }
+
+    for position in portfolio.iter().filter(|position| !position.closed) {
+        let sale = |action, reason: &str| Sale {
+            investment_id: position.investment_id,
+            action,
+            reason: reason.to_string(),
+            loan: platform::Loan::from(position),
+        };
+        let on_sale = position.sm_offer_principal_available > Money::ZERO;
+        let Some(reason) = rules.reason_to_sell(position, today) else {
+            if on_sale && ledger.listed(crate::NAME, position.loan_id as i64)? {
+                sales.push(sale(Action::Cancel, "no longer a reason to sell"));
+            }
+            continue;
+        };
+
+        if on_sale {
+            match position.sm_discount_or_premium_percent {
+                Some(asked) if asked == rules.discount_or_premium => continue,
+                asked => {
+                    let asked = asked.map_or("no".to_string(), |asked| format!("{}%", asked));
+                    sales.push(sale(Action::Cancel, &format!("stale offer at {}", asked)));
+                }
+            }
+        }
+        if position.principal_outstanding > Money::ZERO {
+            let action = Action::List {
+                principal: position.principal_outstanding,
+                discount_or_premium: rules.discount_or_premium,
+            };
+            sales.push(sale(action, &reason));
+        }
+    }
+
+    Ok(sales)
+}
+
+/// Carries out the sales and records them in the ledger. A failed sale is
+/// reported and the remaining ones are still tried.
+pub async fn execute(client: &mut Client, sales: &[Sale], ledger: &Ledger) -> anyhow::Result<()> {
+    for sale in sales {
+        let result = if std::env::var("DRY_RUN").is_ok() {
+            println!("DRY RUN: Would have executed {:?}", sale);
+            Ok("DRY RUN".to_string())
+        } else {
+            match sale.action {
+                Action::List {
+                    principal,
+                    discount_or_premium,
+                } => {
+                    client
+                        .sell_investment(sale.investment_id, principal, discount_or_premium)
+                        .await
+                }
+                Action::Cancel => client.cancel_sale(sale.investment_id).await,
+            }
+        };
+
+        let (outcome, response) = match result {
+            Ok(body) => {
+                println!("Executed {:?}", sale);
+                match sale.action {
+                    Action::List { .. } => (Outcome::Listed, body),
+                    Action::Cancel => (Outcome::Cancelled, body),
+                }
+            }
+            Err(e) => {
+                eprintln!("Failed to execute {:?}: {}", sale, e.report());
+                (Outcome::Failed, e.report())
+            }
+        };
+        let amount = match sale.action {
+            Action::List { principal, .. } => Some(principal),
+            Action::Cancel => None,
+        };
+        ledger.record(&platform::Decision {
+            loan: &sale.loan,
+            outcome,
+            reason: Some(&sale.reason),
+            amount,
+            response: Some(&response),
+        })?;
+    }
+
+    Ok(())
+}
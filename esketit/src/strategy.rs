@@ -0,0 +1,203 @@
+//! Deterministic investment rules, taking the place of the round trip to
+//! the OpenAI API.
+//!
+//! Unlike Peerberry, Esketit doesn't go through `platform::run`: loans and
+//! secondary market offers are ranked against each other and paid from the
+//! same cash, which the shared loop over a single market can't do. It uses
+//! the same [`platform::Allocation`], [`Exposure`] and ledger checks.
+
+use crate::{secondary, Client, Config, State, NAME};
+use chrono::NaiveDate;
+use platform::{Exposure, Holding, Ledger, Money, Outcome};
+
+/// Esketit doesn't accept investments below this amount.
+pub const MIN_INVESTMENT: Money = Money::from_cents(500);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Target {
+    /// A loan on the primary market, by loan id.
+    Loan(u64),
+    /// An offer on the secondary market, by investment id.
+    Investment(u64),
+}
+
+#[derive(Debug, Clone)]
+pub struct Decision {
+    pub target: Target,
+    pub amount: Money,
+    /// The loan invested in, for the ledger.
+    pub loan: platform::Loan,
+}
+
+struct Candidate {
+    target: Target,
+    loan: platform::Loan,
+    /// Interest rate of a loan, estimated yield to maturity of an offer.
+    score: f64,
+    /// What a principal of one costs, one on the primary market.
+    price_ratio: f64,
+}
+
+/// Picks what to invest the cash balance in, only ever loans in the
+/// balance's own currency.
+///
+/// Loans shorter than `max_term_period` and paying at least
+/// `min_interest_rate` are ranked by interest rate, together with the
+/// secondary market offers passing the [`secondary::Rules`](crate::secondary::Rules)
+/// ranked by their estimated yield to maturity. The cash is then spread
+/// over them in that order, keeping within the diversification limits given
+/// what the portfolio already holds and the `allocation` keys of the
+/// config. Loans the ledger says were invested in
+/// before are passed over, as is every other candidate that gets nothing;
+/// those and the loans and offers failing a rule are recorded in the ledger
+/// as skipped.
+pub fn decide(
+    state: &State,
+    config: &Config,
+    ledger: &Ledger,
+    today: NaiveDate,
+) -> anyhow::Result<Vec<Decision>> {
+    let skip = |loan: &platform::Loan, reason: &str| {
+        ledger.record(&platform::Decision {
+            loan,
+            outcome: Outcome::Skipped,
+            reason: Some(reason),
+            amount: None,
+            response: None,
+        })
+    };
+
+    let mut loans = Vec::new();
+    for available in &state.available_loans {
+        let loan = platform::Loan::from(available);
+        if available.term_in_days < 0 || available.term_in_days as u32 > config.max_term_period {
+            skip(&loan, &format!("term of {} days", available.term_in_days))?;
+        } else if available.interest_rate_percent < config.min_interest_rate {
+            skip(
+                &loan,
+                &format!("interest rate of {}%", available.interest_rate_percent),
+            )?;
+        } else {
+            loans.push(Candidate {
+                target: Target::Loan(available.loan_id as u64),
+                loan,
+                score: available.interest_rate_percent as f64,
+                price_ratio: 1.0,
+            });
+        }
+    }
+
+    let rules = &config.secondary_market;
+    let mut investments = Vec::new();
+    for investment in &state.available_investments {
+        if !rules.buy || investment.currency_code != state.currency {
+            continue;
+        }
+        let loan = platform::Loan::from(investment);
+        match rules.check(investment, config, today) {
+            Ok(ytm) => investments.push(Candidate {
+                target: Target::Investment(investment.investment_id as u64),
+                loan,
+                score: ytm,
+                price_ratio: secondary::price_ratio(investment),
+            }),
+            Err(reason) => skip(&loan, &reason)?,
+        }
+    }
+
+    let mut candidates: Vec<Candidate> = loans
+        .into_iter()
+        .chain(investments)
+        .filter(|candidate| candidate.loan.currency == state.currency)
+        .collect();
+    candidates.sort_by(|a, b| {
+        b.score
+            .partial_cmp(&a.score)
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+
+    let allocation = &config.allocation;
+    let minimum = allocation.minimum(MIN_INVESTMENT);
+    let mut cash = allocation.investable(state.cash_balance);
+
+    let holdings: Vec<Holding> = state.portfolio.iter().map(Holding::from).collect();
+    let mut exposure = Exposure::new(&holdings, cash);
+
+    let mut decisions = Vec::new();
+    for candidate in candidates {
+        let loan = &candidate.loan;
+        if cash < minimum {
+            skip(loan, "no funds left")?;
+            continue;
+        }
+        if ledger.holds(NAME, loan.id)? {
+            skip(loan, "already invested")?;
+            continue;
+        }
+        let headroom = exposure.headroom(&config.limits, loan.id, &loan.originator, &loan.country);
+        // The amount is principal, bought at a discount or premium on the
+        // secondary market.
+        let affordable = Money::from_cents((cash.cents() as f64 / candidate.price_ratio) as i64);
+        let upper_bound = affordable.min(loan.available_to_invest).min(headroom);
+        let Some(amount) = allocation.amount(upper_bound, MIN_INVESTMENT) else {
+            skip(loan, &format!("can invest at most {}", upper_bound))?;
+            continue;
+        };
+        exposure.add(&Holding {
+            loan_id: loan.id,
+            originator: loan.originator.clone(),
+            country: loan.country.clone(),
+            amount,
+        });
+        decisions.push(Decision {
+            target: candidate.target,
+            amount,
+            loan: candidate.loan,
+        });
+        cash -= Money::from_cents((amount.cents() as f64 * candidate.price_ratio).ceil() as i64);
+    }
+
+    Ok(decisions)
+}
+
+/// Carries out the decisions and records the outcome in the ledger. A failed
+/// investment is reported and the remaining decisions are still tried.
+pub async fn execute(
+    client: &mut Client,
+    decisions: &[Decision],
+    ledger: &Ledger,
+) -> anyhow::Result<()> {
+    for decision in decisions {
+        let result = if std::env::var("DRY_RUN").is_ok() {
+            println!("DRY RUN: Would have executed {:?}", decision);
+            Ok("DRY RUN".to_string())
+        } else {
+            match decision.target {
+                Target::Loan(loan_id) => client.invest_loan(loan_id, decision.amount).await,
+                Target::Investment(investment_id) => {
+                    client.buy_investment(investment_id, decision.amount).await
+                }
+            }
+        };
+
+        let (outcome, response) = match result {
+            Ok(body) => {
+                println!("Executed {:?}", decision);
+                (Outcome::Invested, body)
+            }
+            Err(e) => {
+                eprintln!("Failed to execute {:?}: {}", decision, e.report());
+                (Outcome::Failed, e.report())
+            }
+        };
+        ledger.record(&platform::Decision {
+            loan: &decision.loan,
+            outcome,
+            reason: None,
+            amount: Some(decision.amount),
+            response: Some(&response),
+        })?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,71 @@
+//! Esketit hands out an XSRF token in the `XSRF-TOKEN` cookie, changes it on
+//! many responses and only accepts requests that change something when the
+//! latest token is copied into the `X-XSRF-TOKEN` header.
+
+use reqwest::{Method, Request, Response, StatusCode};
+use reqwest_middleware::{Middleware, Next, Result};
+use std::sync::Mutex;
+use task_local_extensions::Extensions;
+
+const COOKIE: &str = "XSRF-TOKEN";
+const HEADER: &str = "X-XSRF-TOKEN";
+
+/// Keeps the latest token from any response and sends it along with every
+/// request that isn't a plain read. A request rejected because its token
+/// went stale in the meantime is sent once more with the new token.
+#[derive(Default)]
+pub struct XsrfToken {
+    token: Mutex<Option<String>>,
+}
+
+impl XsrfToken {
+    fn current(&self) -> Option<String> {
+        self.token.lock().unwrap().clone()
+    }
+
+    /// Attaches the current token, returns which one was sent.
+    fn attach(&self, request: &mut Request) -> Option<String> {
+        if matches!(*request.method(), Method::GET | Method::HEAD) {
+            return None;
+        }
+        let token = self.current()?;
+        if let Ok(value) = token.parse() {
+            request.headers_mut().insert(HEADER, value);
+        }
+        Some(token)
+    }
+
+    /// Picks up the token the response hands out, if any.
+    fn update(&self, response: &Response) {
+        if let Some(cookie) = response.cookies().find(|cookie| cookie.name() == COOKIE) {
+            *self.token.lock().unwrap() = Some(cookie.value().to_string());
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Middleware for XsrfToken {
+    async fn handle(
+        &self,
+        mut request: Request,
+        extensions: &mut Extensions,
+        next: Next<'_>,
+    ) -> Result<Response> {
+        let retry = request.try_clone();
+        let sent = self.attach(&mut request);
+        let response = next.clone().run(request, extensions).await?;
+        self.update(&response);
+
+        let stale =
+            sent.is_some() && response.status() == StatusCode::FORBIDDEN && self.current() != sent;
+        match retry {
+            Some(mut retry) if stale => {
+                self.attach(&mut retry);
+                let response = next.run(retry, extensions).await?;
+                self.update(&response);
+                Ok(response)
+            }
+            _ => Ok(response),
+        }
+    }
+}
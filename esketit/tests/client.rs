@@ -0,0 +1,235 @@
+//! The client against [`MockEsketit`], in particular following the
+//! `XSRF-TOKEN` as it changes.
+
+mod common;
+
+use axum::http::StatusCode;
+use common::{holding, loan, loan_in, offer, MockEsketit, Reply, Script};
+use esketit::Client;
+use platform::{Error, Money};
+use serde_json::json;
+
+fn client(mock: &MockEsketit) -> Client {
+    Client::new(mock.config(json!({}))).unwrap()
+}
+
+#[tokio::test]
+async fn fetches_remote_state() {
+    let mock = MockEsketit::start(Script {
+        cash_balance: 42.5,
+        primary_market: vec![loan(1, 12.0, 30), loan(2, 11.0, 45)],
+        secondary_market: vec![offer(3, 13.0, -1.0)],
+        portfolio: vec![holding(4, 25.0)],
+        ..Script::default()
+    });
+
+    let state = client(&mock).fetch_remote_state().await.unwrap().remove(0);
+    assert_eq!(state.cash_balance, Money::from_cents(4250));
+    assert_eq!(state.available_loans.len(), 2);
+    assert_eq!(state.available_investments.len(), 1);
+    assert_eq!(state.portfolio[0].loan_id, 4);
+
+    let script = mock.script();
+    assert_eq!(script.rejected_tokens, 0);
+    // /profile, the account summary, both markets and the portfolio
+    assert_eq!(script.rotations, 5);
+}
+
+#[tokio::test]
+async fn invests_with_the_latest_token() {
+    let mock = MockEsketit::start(Script {
+        primary_market: vec![loan(1, 12.0, 30)],
+        secondary_market: vec![offer(3, 13.0, -1.0)],
+        ..Script::default()
+    });
+    let mut client = client(&mock);
+
+    client.fetch_remote_state().await.unwrap();
+    client
+        .invest_loan(1, Money::from_cents(1000))
+        .await
+        .unwrap();
+    client
+        .buy_investment(3, Money::from_cents(500))
+        .await
+        .unwrap();
+
+    let script = mock.script();
+    assert_eq!(script.investments, vec![(1, "10.00".to_string())]);
+    assert_eq!(script.purchases, vec![(3, "5.00".to_string())]);
+    assert_eq!(script.rejected_tokens, 0);
+}
+
+#[tokio::test]
+async fn retries_an_expired_token_once() {
+    let mock = MockEsketit::start(Script::default());
+    let mut client = client(&mock);
+
+    client.fetch_remote_state().await.unwrap();
+    mock.script().expire_tokens = 1;
+    client
+        .invest_loan(1, Money::from_cents(1000))
+        .await
+        .unwrap();
+
+    let script = mock.script();
+    assert_eq!(script.investments, vec![(1, "10.00".to_string())]);
+    assert_eq!(script.rejected_tokens, 1);
+}
+
+#[tokio::test]
+async fn gives_up_when_the_new_token_is_rejected_too() {
+    let mock = MockEsketit::start(Script::default());
+    let mut client = client(&mock);
+
+    client.fetch_remote_state().await.unwrap();
+    mock.script().expire_tokens = 2;
+    let result = client.invest_loan(1, Money::from_cents(1000)).await;
+    assert!(matches!(result, Err(Error::Auth(_))), "{:?}", result);
+    assert_eq!(mock.script().rejected_tokens, 2);
+}
+
+#[tokio::test]
+async fn investing_without_a_login_is_rejected() {
+    let mock = MockEsketit::start(Script::default());
+
+    let result = client(&mock).invest_loan(1, Money::from_cents(1000)).await;
+    assert!(matches!(result, Err(Error::Auth(_))), "{:?}", result);
+    assert!(mock.script().investments.is_empty());
+}
+
+#[tokio::test]
+async fn pages_through_the_markets() {
+    let mock = MockEsketit::start(Script {
+        primary_market: (1..=45).map(|id| loan(id, 12.0, 30)).collect(),
+        secondary_market: (1..=20).map(|id| offer(id, 12.0, -1.0)).collect(),
+        ..Script::default()
+    });
+
+    let state = client(&mock).fetch_remote_state().await.unwrap().remove(0);
+    assert_eq!(state.available_loans.len(), 45);
+    assert_eq!(state.available_investments.len(), 20);
+    // Three pages of loans, and an empty second page of offers
+    assert_eq!(mock.script().market_pages, vec![1, 2, 3, 1, 2]);
+}
+
+#[tokio::test]
+async fn stops_paging_at_max_loans() {
+    let mock = MockEsketit::start(Script {
+        primary_market: (1..=45).map(|id| loan(id, 12.0, 30)).collect(),
+        ..Script::default()
+    });
+    let mut client = Client::new(mock.config(json!({ "max_loans": 30 }))).unwrap();
+
+    let state = client.fetch_remote_state().await.unwrap().remove(0);
+    assert_eq!(state.available_loans.len(), 30);
+}
+
+#[tokio::test]
+async fn pages_through_the_whole_portfolio() {
+    let mock = MockEsketit::start(Script {
+        portfolio: (1..=45).map(|id| holding(id, 10.0)).collect(),
+        ..Script::default()
+    });
+    // Limits the markets, not what is already held.
+    let mut client = Client::new(mock.config(json!({ "max_loans": 30 }))).unwrap();
+
+    let state = client.fetch_remote_state().await.unwrap().remove(0);
+    assert_eq!(state.portfolio.len(), 45);
+}
+
+#[tokio::test]
+async fn fetches_each_currency_separately() {
+    let mut script = Script {
+        primary_market: vec![loan(1, 12.0, 30), loan_in("PLN", 2, 12.0, 30)],
+        ..Script::default()
+    };
+    script.other_cash_balances.insert("PLN".to_string(), 250.0);
+    let mock = MockEsketit::start(script);
+    let mut client = Client::new(mock.config(json!({ "currencies": ["EUR", "PLN"] }))).unwrap();
+
+    let states = client.fetch_remote_state().await.unwrap();
+    assert_eq!(states.len(), 2);
+    assert_eq!(states[1].currency, "PLN");
+    assert_eq!(states[1].cash_balance, Money::from_cents(25000));
+    assert_eq!(states[1].available_loans[0].loan_id, 2);
+    assert_eq!(states[0].available_loans[0].loan_id, 1);
+}
+
+#[tokio::test]
+async fn rejected_login_is_an_auth_error() {
+    let mock = MockEsketit::start(Script {
+        login: Reply::error(StatusCode::BAD_REQUEST, "Invalid credentials"),
+        ..Script::default()
+    });
+
+    let e = client(&mock).fetch_remote_state().await.err().unwrap();
+    assert!(matches!(e.downcast_ref(), Some(Error::Auth(_))), "{:?}", e);
+}
+
+#[tokio::test]
+async fn rejected_totp_is_a_two_factor_error() {
+    let mock = MockEsketit::start(Script {
+        confirm_login: Reply::error(StatusCode::BAD_REQUEST, "Invalid code"),
+        ..Script::default()
+    });
+
+    let e = client(&mock).fetch_remote_state().await.err().unwrap();
+    assert!(
+        matches!(e.downcast_ref(), Some(Error::TwoFactor(_))),
+        "{:?}",
+        e
+    );
+}
+
+#[tokio::test]
+async fn low_cash_still_fetches_the_portfolio() {
+    let mock = MockEsketit::start(Script {
+        cash_balance: 2.0,
+        portfolio: vec![holding(4, 25.0)],
+        ..Script::default()
+    });
+
+    let state = client(&mock).fetch_remote_state().await.unwrap().remove(0);
+    assert_eq!(state.cash_balance, Money::from_cents(200));
+    assert_eq!(state.portfolio.len(), 1);
+}
+
+#[tokio::test]
+async fn reads_the_portfolio_without_the_markets() {
+    let mock = MockEsketit::start(Script {
+        primary_market: vec![loan(1, 12.0, 30)],
+        portfolio: vec![holding(4, 25.0), holding(5, 10.0)],
+        ..Script::default()
+    });
+    let mut client = client(&mock);
+
+    client.login().await.unwrap();
+    let portfolio = client.portfolio("EUR").await.unwrap();
+    let summary = client.account_summary("EUR").await.unwrap();
+    assert_eq!(portfolio.len(), 2);
+    assert_eq!(summary.cash_balance, Money::from_cents(10000));
+
+    let script = mock.script();
+    assert!(script.market_pages.is_empty());
+    assert_eq!(script.rejected_tokens, 0);
+}
+
+#[tokio::test]
+async fn fully_funded_loan_is_unavailable() {
+    let mut script = Script::default();
+    script.invest.insert(
+        1,
+        Reply::error(StatusCode::BAD_REQUEST, "Loan is fully funded"),
+    );
+    let mock = MockEsketit::start(script);
+    let mut client = client(&mock);
+
+    client.fetch_remote_state().await.unwrap();
+    let result = client.invest_loan(1, Money::from_cents(1000)).await;
+    assert!(
+        matches!(result, Err(Error::LoanUnavailable(_))),
+        "{:?}",
+        result
+    );
+}
@@ -0,0 +1,524 @@
+//! A local stand-in for the Esketit API, so the client can be tested without
+//! network access. Like Esketit it keeps the login in a session cookie and
+//! hands out a new `XSRF-TOKEN` cookie on several responses; requests that
+//! change something are only accepted with the latest token in the
+//! `X-XSRF-TOKEN` header.
+
+// Each test file uses a different part of the fake.
+#![allow(dead_code)]
+
+use axum::extract::State;
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::net::TcpListener;
+use std::sync::{Arc, Mutex};
+
+/// A canned response.
+#[derive(Clone)]
+pub struct Reply {
+    pub status: StatusCode,
+    pub body: Value,
+}
+
+impl Reply {
+    pub fn ok(body: Value) -> Reply {
+        Reply {
+            status: StatusCode::OK,
+            body,
+        }
+    }
+
+    pub fn error(status: StatusCode, message: &str) -> Reply {
+        Reply {
+            status,
+            body: json!({ "message": message }),
+        }
+    }
+}
+
+impl IntoResponse for Reply {
+    fn into_response(self) -> Response {
+        (self.status, Json(self.body)).into_response()
+    }
+}
+
+/// What the fake answers, and what it was asked.
+pub struct Script {
+    pub login: Reply,
+    pub confirm_login: Reply,
+    /// The EUR balance.
+    pub cash_balance: f64,
+    /// Balances in other currencies, by ISO code.
+    pub other_cash_balances: HashMap<String, f64>,
+    pub primary_market: Vec<Value>,
+    pub secondary_market: Vec<Value>,
+    pub portfolio: Vec<Value>,
+    /// Answers to investments in these loans, success for any other loan.
+    pub invest: HashMap<u64, Reply>,
+
+    /// `pending` after the password, `authenticated` after the TOTP.
+    pub session: Option<&'static str>,
+    /// The token requests have to send, `None` until `/profile`.
+    pub xsrf_token: Option<String>,
+    /// How many tokens were handed out.
+    pub rotations: usize,
+    /// Requests that came with a missing or outdated token.
+    pub rejected_tokens: usize,
+    /// Reject this many of the next tokens, even the latest, as if they
+    /// expired in the meantime.
+    pub expire_tokens: usize,
+    /// Page number asked for by each market query.
+    pub market_pages: Vec<u64>,
+    /// Loan id and amount of each successful investment.
+    pub investments: Vec<(u64, String)>,
+    /// Investment id and amount of each secondary market purchase.
+    pub purchases: Vec<(u64, String)>,
+    /// Investment id, principal and discount or premium of each position
+    /// put up for sale.
+    pub sales: Vec<(u64, String, String)>,
+    /// Investment id of each offer taken off the market.
+    pub cancellations: Vec<u64>,
+}
+
+impl Default for Script {
+    fn default() -> Self {
+        Script {
+            login: Reply::ok(json!({ "twoFactorRequired": true })),
+            confirm_login: Reply::ok(json!({})),
+            cash_balance: 100.0,
+            other_cash_balances: HashMap::new(),
+            primary_market: Vec::new(),
+            secondary_market: Vec::new(),
+            portfolio: Vec::new(),
+            invest: HashMap::new(),
+            session: None,
+            xsrf_token: None,
+            rotations: 0,
+            rejected_tokens: 0,
+            expire_tokens: 0,
+            market_pages: Vec::new(),
+            investments: Vec::new(),
+            purchases: Vec::new(),
+            sales: Vec::new(),
+            cancellations: Vec::new(),
+        }
+    }
+}
+
+impl Script {
+    /// Hands out a new token, the previous one is no longer accepted.
+    fn rotate(&mut self) -> [(header::HeaderName, String); 1] {
+        self.rotations += 1;
+        let token = format!("xsrf-{}", self.rotations);
+        self.xsrf_token = Some(token.clone());
+        [(header::SET_COOKIE, format!("XSRF-TOKEN={}; Path=/", token))]
+    }
+
+    fn logged_in(&self, headers: &HeaderMap) -> bool {
+        self.session == Some("authenticated") && cookie(headers, "SESSION") == Some("authenticated")
+    }
+
+    /// Checks the session and the token of a request that changes something,
+    /// the answer when they don't pass. A rejected token is answered with a
+    /// new one, like Esketit does.
+    fn reject(&mut self, headers: &HeaderMap) -> Option<Response> {
+        if !self.logged_in(headers) {
+            return Some(Reply::error(StatusCode::UNAUTHORIZED, "Not logged in").into_response());
+        }
+        let token = headers
+            .get("X-XSRF-TOKEN")
+            .and_then(|value| value.to_str().ok());
+        let expired = self.expire_tokens > 0;
+        if expired || token.is_none() || token != self.xsrf_token.as_deref() {
+            self.expire_tokens = self.expire_tokens.saturating_sub(1);
+            self.rejected_tokens += 1;
+            let reply = Reply::error(StatusCode::FORBIDDEN, "Invalid XSRF token");
+            return Some((self.rotate(), reply).into_response());
+        }
+        None
+    }
+}
+
+type Shared = Arc<Mutex<Script>>;
+
+fn cookie<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'))
+        .filter_map(|pair| pair.trim().split_once('='))
+        .find(|(key, _)| *key == name)
+        .map(|(_, value)| value)
+}
+
+pub fn loan(id: i64, interest_rate: f32, term: i32) -> Value {
+    loan_in("EUR", id, interest_rate, term)
+}
+
+pub fn loan_in(currency: &str, id: i64, interest_rate: f32, term: i32) -> Value {
+    json!({
+        "loanId": id,
+        "issueDate": "2024-01-01",
+        "interestRatePercent": interest_rate,
+        "principalIssued": 1000.0,
+        "principalOffer": 500.0,
+        "principalOutstanding": 1000.0,
+        "currencyCode": currency,
+        "currencySymbol": "€",
+        "totalPayments": 1,
+        "openPayments": 1,
+        "closedPayments": 0,
+        "maturityDate": "2024-03-01",
+        "nextPaymentDate": "2024-03-01",
+        "termInDays": term,
+        "originatorCompanyName": "Originator",
+        "originatorId": id % 3,
+        "productCode": "PDL",
+        "productLabel": "Payday loan",
+        "countryCode": "LV",
+        "hasBuyback": true,
+        "extensions": 0,
+        "extendedForDays": 0,
+        "myInvestments": 0.0,
+        "myInvestmentsPercent": 0.0,
+        "fundedPercent": 50,
+        "amountFunded": 500.0,
+        "amountAvailable": 500.0,
+        "availablePercent": 50,
+        "loanStatus": "ACTIVE",
+    })
+}
+
+pub fn offer(id: i64, interest_rate: f32, discount: f64) -> Value {
+    json!({
+        "investmentId": id,
+        "loanId": id + 1000,
+        "issueDate": "2024-01-01",
+        "interestRatePercent": interest_rate,
+        "currencyCode": "EUR",
+        "currencySymbol": "€",
+        "totalPayments": 1,
+        "openPayments": 1,
+        "closedPayments": 0,
+        "maturityDate": "2024-03-01",
+        "nextPaymentDate": "2024-03-01",
+        "termInDays": 30,
+        "originatorCompanyName": "Originator",
+        "originatorId": id % 3,
+        "productCode": "PDL",
+        "productLabel": "Payday loan",
+        "countryCode": "LV",
+        "collectionStatus": "CURRENT",
+        "smOfferPrincipalAvailable": 50.0,
+        "smDiscountOrPremiumPercent": discount,
+        "smPrice": 50.0 * (1.0 + discount / 100.0),
+    })
+}
+
+pub fn holding(loan_id: u64, outstanding: f64) -> Value {
+    json!({
+        "investmentId": loan_id + 5000,
+        "loanId": loan_id,
+        "interestRatePercent": 12.0,
+        "investmentDate": "2024-01-02",
+        "issueDate": "2024-01-01",
+        "maturityDate": "2024-03-01",
+        "nextPaymentDate": "2024-03-01",
+        "termInDays": 30,
+        "totalPayments": 1,
+        "openPayments": 1,
+        "closedPayments": 0,
+        "originatorCompanyName": "Originator",
+        "originatorId": loan_id % 3,
+        "productCode": "PDL",
+        "productLabel": "Payday loan",
+        "countryCode": "LV",
+        "collectionStatus": "CURRENT",
+        "closed": false,
+        "principalInvested": outstanding,
+        "principalOutstanding": outstanding,
+        "principalPaid": 0.0,
+        "principalPending": 0.0,
+        "principalReceived": 0.0,
+        "interestPaid": 0.0,
+        "interestBonusPaid": 0.0,
+        "interestPending": 0.0,
+        "interestReceived": 0.0,
+        "bonusPaid": 0.0,
+        "bonusPending": 0.0,
+        "bonusReceived": 0.0,
+        "totalPending": 0.0,
+        "smOfferPrincipalAvailable": 0.0,
+        "smPrincipalSold": 0.0,
+        "smDiscountOrPremiumPercent": null,
+        "currencyCode": "EUR",
+        "currencySymbol": "€",
+        "agreementFileName": "agreement.pdf",
+        "agreementFileReference": "ref",
+    })
+}
+
+pub struct MockEsketit {
+    pub url: String,
+    pub script: Shared,
+}
+
+impl MockEsketit {
+    /// Starts the fake on a free local port.
+    pub fn start(script: Script) -> MockEsketit {
+        let script = Arc::new(Mutex::new(script));
+        let app = Router::new()
+            .route("/public/login", post(login))
+            .route("/public/confirm-login", post(confirm_login))
+            .route("/profile", get(profile))
+            .route("/account-summary", post(account_summary))
+            .route("/public/query-primary-market", post(primary_market))
+            .route("/public/query-secondary-market", post(secondary_market))
+            .route("/query-my-investments", post(query_my_investments))
+            .route("/invest", post(invest))
+            .route("/buy-investment", post(buy_investment))
+            .route("/sell-investment", post(sell_investment))
+            .route("/cancel-sm-offer", post(cancel_sm_offer))
+            .route("/totp", get(totp))
+            .with_state(script.clone());
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let server = axum::Server::from_tcp(listener)
+            .unwrap()
+            .serve(app.into_make_service());
+        tokio::spawn(server);
+
+        MockEsketit { url, script }
+    }
+
+    pub fn script(&self) -> std::sync::MutexGuard<'_, Script> {
+        self.script.lock().unwrap()
+    }
+
+    /// A client config pointing at the fake.
+    pub fn config(&self, extra: Value) -> esketit::Config {
+        let mut config = json!({
+            "username": "investor@example.com",
+            "password": "secret",
+            "max_term_period": 60,
+            "min_interest_rate": 10.0,
+            "tfa_url": format!("{}/totp", self.url),
+            "base_url": self.url,
+        });
+        if let (Some(config), Some(extra)) = (config.as_object_mut(), extra.as_object()) {
+            config.extend(extra.clone());
+        }
+        serde_json::from_value(config).unwrap()
+    }
+}
+
+async fn login(State(script): State<Shared>) -> Response {
+    let mut script = script.lock().unwrap();
+    let reply = script.login.clone();
+    if !reply.status.is_success() {
+        return reply.into_response();
+    }
+    script.session = Some("pending");
+    ([(header::SET_COOKIE, "SESSION=pending; Path=/")], reply).into_response()
+}
+
+async fn confirm_login(
+    State(script): State<Shared>,
+    headers: HeaderMap,
+    Json(payload): Json<Value>,
+) -> Response {
+    let mut script = script.lock().unwrap();
+    if script.session != Some("pending") || cookie(&headers, "SESSION") != Some("pending") {
+        return Reply::error(StatusCode::UNAUTHORIZED, "Log in first").into_response();
+    }
+    if payload["totp"] != "123456" {
+        return Reply::error(StatusCode::BAD_REQUEST, "Invalid code").into_response();
+    }
+    let reply = script.confirm_login.clone();
+    if !reply.status.is_success() {
+        return reply.into_response();
+    }
+    script.session = Some("authenticated");
+    (
+        [(header::SET_COOKIE, "SESSION=authenticated; Path=/")],
+        reply,
+    )
+        .into_response()
+}
+
+async fn profile(State(script): State<Shared>, headers: HeaderMap) -> Response {
+    let mut script = script.lock().unwrap();
+    if !script.logged_in(&headers) {
+        return Reply::error(StatusCode::UNAUTHORIZED, "Not logged in").into_response();
+    }
+    (
+        script.rotate(),
+        Json(json!({ "email": "investor@example.com" })),
+    )
+        .into_response()
+}
+
+async fn account_summary(
+    State(script): State<Shared>,
+    headers: HeaderMap,
+    Json(request): Json<Value>,
+) -> Response {
+    let mut script = script.lock().unwrap();
+    if let Some(response) = script.reject(&headers) {
+        return response;
+    }
+    let cash_balance = match request["currencyCode"].as_str() {
+        Some("EUR") => script.cash_balance,
+        Some(currency) => script
+            .other_cash_balances
+            .get(currency)
+            .copied()
+            .unwrap_or(0.0),
+        None => return Reply::error(StatusCode::BAD_REQUEST, "No currency").into_response(),
+    };
+    (
+        script.rotate(),
+        Json(json!({ "cashBalance": cash_balance })),
+    )
+        .into_response()
+}
+
+/// The requested page of the `items` in the requested currency, and a new
+/// token.
+fn page(script: &mut Script, items: &[Value], request: &Value) -> Response {
+    let page = request["page"].as_u64().unwrap_or(1);
+    let page_size = request["pageSize"].as_u64().unwrap_or(20);
+    let currency = &request["filter"]["currencyCode"];
+    let items: Vec<Value> = items
+        .iter()
+        .filter(|item| item["currencyCode"] == *currency)
+        .skip(((page - 1) * page_size) as usize)
+        .take(page_size as usize)
+        .cloned()
+        .collect();
+    (script.rotate(), Json(json!({ "items": items }))).into_response()
+}
+
+async fn primary_market(State(script): State<Shared>, Json(request): Json<Value>) -> Response {
+    let mut script = script.lock().unwrap();
+    let items = script.primary_market.clone();
+    script
+        .market_pages
+        .push(request["page"].as_u64().unwrap_or(1));
+    page(&mut script, &items, &request)
+}
+
+async fn secondary_market(State(script): State<Shared>, Json(request): Json<Value>) -> Response {
+    let mut script = script.lock().unwrap();
+    let bound = |key: &str| {
+        request["filter"][key]
+            .as_str()
+            .map(|v| v.parse::<f64>().unwrap())
+    };
+    let (from, to) = (
+        bound("smDiscountOrPremiumPercentFrom"),
+        bound("smDiscountOrPremiumPercentTo"),
+    );
+    let items: Vec<Value> = script
+        .secondary_market
+        .iter()
+        .filter(|item| {
+            let discount = item["smDiscountOrPremiumPercent"].as_f64().unwrap();
+            from.is_none_or(|from| discount >= from) && to.is_none_or(|to| discount <= to)
+        })
+        .cloned()
+        .collect();
+    script
+        .market_pages
+        .push(request["page"].as_u64().unwrap_or(1));
+    page(&mut script, &items, &request)
+}
+
+async fn query_my_investments(
+    State(script): State<Shared>,
+    headers: HeaderMap,
+    Json(request): Json<Value>,
+) -> Response {
+    let mut script = script.lock().unwrap();
+    if let Some(response) = script.reject(&headers) {
+        return response;
+    }
+    let items = script.portfolio.clone();
+    page(&mut script, &items, &request)
+}
+
+async fn invest(
+    State(script): State<Shared>,
+    headers: HeaderMap,
+    Json(request): Json<Value>,
+) -> Response {
+    let mut script = script.lock().unwrap();
+    if let Some(response) = script.reject(&headers) {
+        return response;
+    }
+    let loan_id = request["loanId"].as_u64().unwrap();
+    if let Some(reply) = script.invest.get(&loan_id) {
+        return reply.clone().into_response();
+    }
+    let amount = request["amount"].as_str().unwrap().to_string();
+    script.investments.push((loan_id, amount));
+    Reply::ok(json!({ "status": "ok" })).into_response()
+}
+
+async fn buy_investment(
+    State(script): State<Shared>,
+    headers: HeaderMap,
+    Json(request): Json<Value>,
+) -> Response {
+    let mut script = script.lock().unwrap();
+    if let Some(response) = script.reject(&headers) {
+        return response;
+    }
+    let investment_id = request["investmentId"].as_u64().unwrap();
+    let amount = request["amount"].as_str().unwrap().to_string();
+    script.purchases.push((investment_id, amount));
+    Reply::ok(json!({ "status": "ok" })).into_response()
+}
+
+async fn sell_investment(
+    State(script): State<Shared>,
+    headers: HeaderMap,
+    Json(request): Json<Value>,
+) -> Response {
+    let mut script = script.lock().unwrap();
+    if let Some(response) = script.reject(&headers) {
+        return response;
+    }
+    let investment_id = request["investmentId"].as_u64().unwrap();
+    let principal = request["principal"].as_str().unwrap().to_string();
+    let discount = request["discountOrPremiumPercent"]
+        .as_str()
+        .unwrap()
+        .to_string();
+    script.sales.push((investment_id, principal, discount));
+    Reply::ok(json!({ "status": "ok" })).into_response()
+}
+
+async fn cancel_sm_offer(
+    State(script): State<Shared>,
+    headers: HeaderMap,
+    Json(request): Json<Value>,
+) -> Response {
+    let mut script = script.lock().unwrap();
+    if let Some(response) = script.reject(&headers) {
+        return response;
+    }
+    let investment_id = request["investmentId"].as_u64().unwrap();
+    script.cancellations.push(investment_id);
+    Reply::ok(json!({ "status": "ok" })).into_response()
+}
+
+async fn totp() -> Json<Value> {
+    Json(json!({ "totp": "123456" }))
+}
@@ -0,0 +1,148 @@
+//! Putting positions up for sale on [`MockEsketit`].
+
+mod common;
+
+use chrono::NaiveDate;
+use common::{holding, MockEsketit, Script};
+use esketit::{sell, Client};
+use platform::Ledger;
+use serde_json::{json, Value};
+use std::path::Path;
+
+/// The positions of [`common::holding`] mature four weeks later.
+fn today() -> NaiveDate {
+    NaiveDate::from_ymd_opt(2024, 2, 2).unwrap()
+}
+
+/// Plans and carries out the sales of the portfolio, returns the fake to
+/// see what was sold.
+async fn sell(portfolio: Vec<Value>, selling: Value) -> MockEsketit {
+    let mut ledger = Ledger::open(Path::new(":memory:")).unwrap();
+    sell_in(&mut ledger, portfolio, selling).await
+}
+
+/// Like [`sell`], in a new run of `ledger`.
+async fn sell_in(ledger: &mut Ledger, portfolio: Vec<Value>, selling: Value) -> MockEsketit {
+    let mock = MockEsketit::start(Script {
+        portfolio,
+        ..Script::default()
+    });
+    let mut client = Client::new(mock.config(json!({ "selling": selling }))).unwrap();
+    let state = client.fetch_remote_state().await.unwrap().remove(0);
+
+    ledger.start_run("esketit", false).unwrap();
+    let sales = sell::plan(&state.portfolio, &client.config().selling, ledger, today()).unwrap();
+    sell::execute(&mut client, &sales, ledger).await.unwrap();
+    ledger.finish_run().unwrap();
+    mock
+}
+
+fn with(mut position: Value, key: &str, value: Value) -> Value {
+    position[key] = value;
+    position
+}
+
+#[tokio::test]
+async fn sells_late_positions() {
+    let mock = sell(
+        vec![
+            holding(1, 25.0),
+            with(holding(2, 30.0), "collectionStatus", json!("LATE_1_15")),
+        ],
+        json!({ "sell": true, "discount_or_premium": -3.0 }),
+    )
+    .await;
+
+    let script = mock.script();
+    assert_eq!(
+        script.sales,
+        vec![(5002, "30.00".to_string(), "-3".to_string())]
+    );
+}
+
+#[tokio::test]
+async fn sells_positions_maturing_too_late() {
+    let mock = sell(
+        vec![
+            holding(1, 25.0),
+            with(holding(2, 30.0), "maturityDate", json!("2024-12-01")),
+        ],
+        json!({ "sell": true, "max_remaining_days": 60 }),
+    )
+    .await;
+
+    let script = mock.script();
+    assert_eq!(
+        script.sales,
+        vec![(5002, "30.00".to_string(), "0".to_string())]
+    );
+}
+
+#[tokio::test]
+async fn lists_stale_offers_again() {
+    let late = with(holding(1, 25.0), "collectionStatus", json!("LATE_1_15"));
+    let on_sale = with(late, "smOfferPrincipalAvailable", json!(25.0));
+    let mock = sell(
+        vec![
+            with(on_sale.clone(), "smDiscountOrPremiumPercent", json!(-1.0)),
+            with(
+                with(on_sale, "investmentId", json!(5002)),
+                "smDiscountOrPremiumPercent",
+                json!(-5.0),
+            ),
+        ],
+        json!({ "sell": true, "discount_or_premium": -5.0 }),
+    )
+    .await;
+
+    let script = mock.script();
+    assert_eq!(script.cancellations, vec![5001]);
+    assert_eq!(
+        script.sales,
+        vec![(5001, "25.00".to_string(), "-5".to_string())]
+    );
+}
+
+#[tokio::test]
+async fn sells_nothing_unless_enabled() {
+    let mock = sell(
+        vec![with(holding(1, 25.0), "collectionStatus", json!("DEFAULT"))],
+        json!({}),
+    )
+    .await;
+
+    let script = mock.script();
+    assert!(script.sales.is_empty());
+    assert!(script.cancellations.is_empty());
+}
+
+#[tokio::test]
+async fn cancels_its_offers_with_no_reason_to_sell() {
+    let selling = json!({ "sell": true, "discount_or_premium": -5.0 });
+    let mut ledger = Ledger::open(Path::new(":memory:")).unwrap();
+    let late = with(holding(1, 25.0), "collectionStatus", json!("LATE_1_15"));
+    sell_in(&mut ledger, vec![late], selling.clone()).await;
+
+    // Back to current while on sale
+    let on_sale = with(holding(1, 25.0), "smOfferPrincipalAvailable", json!(25.0));
+    let on_sale = with(on_sale, "smDiscountOrPremiumPercent", json!(-5.0));
+    let mock = sell_in(&mut ledger, vec![on_sale], selling).await;
+
+    let script = mock.script();
+    assert_eq!(script.cancellations, vec![5001]);
+    assert!(script.sales.is_empty());
+}
+
+#[tokio::test]
+async fn leaves_offers_made_on_the_website_alone() {
+    let on_sale = with(holding(1, 25.0), "smOfferPrincipalAvailable", json!(25.0));
+    let mock = sell(
+        vec![with(on_sale, "smDiscountOrPremiumPercent", json!(-5.0))],
+        json!({ "sell": true, "discount_or_premium": -5.0 }),
+    )
+    .await;
+
+    let script = mock.script();
+    assert!(script.cancellations.is_empty());
+    assert!(script.sales.is_empty());
+}
@@ -0,0 +1,221 @@
+//! Deciding on the state fetched from [`MockEsketit`], in particular the
+//! secondary market rules.
+
+mod common;
+
+use chrono::NaiveDate;
+use common::{loan, offer, MockEsketit, Script};
+use esketit::strategy::{self, Decision, Target};
+use esketit::Client;
+use platform::{Ledger, Money};
+use serde_json::{json, Value};
+use std::path::Path;
+
+/// The offers of [`common::offer`] mature four weeks later.
+fn today() -> NaiveDate {
+    NaiveDate::from_ymd_opt(2024, 2, 2).unwrap()
+}
+
+async fn decide(script: Script, config: Value) -> Vec<Decision> {
+    let mock = MockEsketit::start(script);
+    let mut client = Client::new(mock.config(config)).unwrap();
+    let state = client.fetch_remote_state().await.unwrap().remove(0);
+
+    let mut ledger = Ledger::open(Path::new(":memory:")).unwrap();
+    ledger.start_run("esketit", false).unwrap();
+    strategy::decide(&state, client.config(), &ledger, today()).unwrap()
+}
+
+fn targets(decisions: &[Decision]) -> Vec<Target> {
+    decisions.iter().map(|decision| decision.target).collect()
+}
+
+#[tokio::test]
+async fn ranks_offers_by_yield_to_maturity() {
+    let decisions = decide(
+        Script {
+            cash_balance: 1000.0,
+            primary_market: vec![loan(1, 20.0, 30)],
+            secondary_market: vec![offer(2, 11.0, -0.5), offer(3, 11.0, -2.0)],
+            ..Script::default()
+        },
+        json!({}),
+    )
+    .await;
+
+    // Four weeks at a 2% discount beats 20% a year at par, 0.5% doesn't.
+    assert_eq!(
+        targets(&decisions),
+        vec![
+            Target::Investment(3),
+            Target::Loan(1),
+            Target::Investment(2)
+        ]
+    );
+}
+
+#[tokio::test]
+async fn asks_the_market_for_the_configured_discounts() {
+    let decisions = decide(
+        Script {
+            secondary_market: vec![
+                offer(1, 12.0, -5.0),
+                offer(2, 12.0, -1.0),
+                offer(3, 12.0, 1.0),
+            ],
+            ..Script::default()
+        },
+        json!({ "secondary_market": {
+            "discount_or_premium_from": -10.0,
+            "discount_or_premium_to": -2.0,
+        } }),
+    )
+    .await;
+
+    assert_eq!(targets(&decisions), vec![Target::Investment(1)]);
+}
+
+#[tokio::test]
+async fn applies_the_offer_rules() {
+    let mut far = offer(1, 12.0, -1.0);
+    far["maturityDate"] = json!("2024-12-01");
+    let mut excluded = offer(2, 12.0, -1.0);
+    excluded["originatorCompanyName"] = json!("Excluded");
+    let low_interest = offer(3, 10.5, -1.0);
+    let cheap = offer(4, 12.0, -1.0);
+
+    let decisions = decide(
+        Script {
+            secondary_market: vec![far, excluded, low_interest, cheap],
+            ..Script::default()
+        },
+        json!({ "secondary_market": {
+            "max_remaining_days": 60,
+            "min_interest_rate": 11.0,
+            "exclude_originators": ["Excluded"],
+        } }),
+    )
+    .await;
+
+    assert_eq!(targets(&decisions), vec![Target::Investment(4)]);
+}
+
+#[tokio::test]
+async fn only_buys_from_allowed_originators() {
+    let decisions = decide(
+        Script {
+            // The originator id is the offer id modulo three.
+            secondary_market: vec![offer(1, 12.0, -1.0), offer(2, 12.0, -1.0)],
+            ..Script::default()
+        },
+        json!({ "secondary_market": { "originators": ["2"] } }),
+    )
+    .await;
+
+    assert_eq!(targets(&decisions), vec![Target::Investment(2)]);
+}
+
+#[tokio::test]
+async fn skips_offers_below_the_minimum_yield() {
+    let decisions = decide(
+        Script {
+            secondary_market: vec![offer(1, 12.0, -0.5), offer(2, 12.0, -2.0)],
+            ..Script::default()
+        },
+        json!({ "secondary_market": { "min_yield": 30.0 } }),
+    )
+    .await;
+
+    assert_eq!(targets(&decisions), vec![Target::Investment(2)]);
+}
+
+#[tokio::test]
+async fn pays_the_discounted_price() {
+    let decisions = decide(
+        Script {
+            cash_balance: 30.0,
+            secondary_market: vec![offer(1, 12.0, -2.0)],
+            ..Script::default()
+        },
+        json!({}),
+    )
+    .await;
+
+    // 30.00 buys 30.61 of principal at 98%.
+    assert_eq!(decisions[0].amount, Money::from_cents(3061));
+}
+
+#[tokio::test]
+async fn buying_can_be_turned_off() {
+    let decisions = decide(
+        Script {
+            primary_market: vec![loan(1, 12.0, 30)],
+            secondary_market: vec![offer(2, 12.0, -2.0)],
+            ..Script::default()
+        },
+        json!({ "secondary_market": { "buy": false } }),
+    )
+    .await;
+
+    assert_eq!(targets(&decisions), vec![Target::Loan(1)]);
+}
+
+#[tokio::test]
+async fn spreads_the_cash_as_allocated() {
+    let decisions = decide(
+        Script {
+            cash_balance: 100.0,
+            primary_market: vec![loan(1, 14.0, 30), loan(2, 13.0, 30), loan(3, 12.0, 30)],
+            ..Script::default()
+        },
+        json!({ "max_per_loan": 30.0, "min_per_loan": 20.0, "cash_reserve": 45.0 }),
+    )
+    .await;
+
+    // 55 to invest: 30 in the first loan, 25 in the second, the 0 left
+    // isn't enough for the third.
+    let amounts: Vec<Money> = decisions.iter().map(|decision| decision.amount).collect();
+    assert_eq!(targets(&decisions), vec![Target::Loan(1), Target::Loan(2)]);
+    assert_eq!(
+        amounts,
+        vec![Money::from_cents(3000), Money::from_cents(2500)]
+    );
+}
+
+#[tokio::test]
+async fn passes_over_overdue_and_late_offers() {
+    // Past its maturity date, a discount would annualise to a huge yield.
+    let mut overdue = offer(1, 12.0, -2.0);
+    overdue["maturityDate"] = json!("2024-01-20");
+    let mut late = offer(2, 12.0, -2.0);
+    late["collectionStatus"] = json!("LATE_1_15");
+    let current = offer(3, 12.0, -0.5);
+
+    let decisions = decide(
+        Script {
+            secondary_market: vec![overdue, late, current],
+            ..Script::default()
+        },
+        json!({}),
+    )
+    .await;
+
+    assert_eq!(targets(&decisions), vec![Target::Investment(3)]);
+}
+
+#[tokio::test]
+async fn buys_the_configured_collection_statuses() {
+    let mut late = offer(1, 12.0, -2.0);
+    late["collectionStatus"] = json!("LATE_1_15");
+
+    let decisions = decide(
+        Script {
+            secondary_market: vec![late],
+            ..Script::default()
+        },
+        json!({ "secondary_market": { "collection_statuses": ["CURRENT", "LATE_1_15"] } }),
+    )
+    .await;
+
+    assert_eq!(targets(&decisions), vec![Target::Investment(1)]);
+}
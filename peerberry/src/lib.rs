@@ -0,0 +1,458 @@
+use log::{error, info};
+use platform::error::{Error, Result};
+use platform::Money;
+use serde_json::json;
+use std::env;
+
+pub mod market;
+pub mod portfolio;
+pub mod report;
+pub mod session;
+
+use market::{LoanPages, LoanQuery};
+use session::Session;
+
+const BASE_URL: &str = "https://api.peerberry.com";
+
+/// Peerberry doesn't accept investments below this amount.
+pub const MIN_INVESTMENT: Money = Money::from_cents(1000);
+
+#[derive(serde::Serialize)]
+struct LoginRequest {
+    email: String,
+    password: String,
+}
+
+#[derive(serde::Deserialize)]
+struct LoginResponse {
+    tfa_is_active: bool,
+    tfa_token: String,
+}
+
+#[derive(serde::Deserialize, Debug)]
+pub struct Login2faResponse {
+    pub access_token: String,
+    pub expires_in: u64,
+    pub refresh_token: String,
+    pub status: String,
+}
+
+#[derive(serde::Deserialize)]
+pub struct Config {
+    pub email: String,
+    pub password: String,
+    pub max_loan_term: i32,
+    pub min_interest: f32,
+    #[serde(deserialize_with = "platform::deserialize_url")]
+    pub tfa_url: url::Url,
+    /// Signs the requests to the `tfa` server.
+    pub tfa_auth: Option<platform::TfaAuth>,
+    /// The client certificate for a `tfa` server behind mutual TLS.
+    pub tfa_tls: Option<platform::TfaTls>,
+    /// Where the Peerberry API lives, only changed for testing.
+    #[serde(
+        default = "default_base_url",
+        deserialize_with = "platform::deserialize_url"
+    )]
+    pub base_url: url::Url,
+    /// Look at no more than this many of the newest loans, all when unset.
+    pub max_loans: Option<usize>,
+    /// Only loans from these countries (ISO codes), any when empty.
+    #[serde(default)]
+    pub countries: Vec<String>,
+    /// Only loans from these originators (ids), any when empty.
+    #[serde(default)]
+    pub originators: Vec<i64>,
+    /// Only loans with a buyback guarantee.
+    #[serde(default)]
+    pub buyback_only: bool,
+    /// ISO codes of the currencies to invest in.
+    #[serde(default = "platform::default_currencies")]
+    pub currencies: Vec<String>,
+    #[serde(flatten)]
+    pub limits: platform::Limits,
+    #[serde(flatten)]
+    pub allocation: platform::Allocation,
+    /// Used with `--daemon`.
+    #[serde(flatten)]
+    pub schedule: platform::daemon::Schedule,
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct AccountInfo {
+    #[serde(rename = "currencyIso")]
+    pub currency_iso: String,
+    #[serde(rename = "availableMoney")]
+    pub available_money: Money,
+    #[serde(rename = "invested")]
+    pub invested: Money,
+    #[serde(rename = "totalProfit")]
+    pub total_profit: Money,
+    #[serde(rename = "totalBalance")]
+    pub total_balance: Money,
+    #[serde(rename = "balanceGrowth", deserialize_with = "string_to_f64")]
+    pub balance_growth: f64,
+    #[serde(rename = "balanceGrowthAmount")]
+    pub balance_growth_amount: Money,
+}
+
+#[derive(serde::Deserialize)]
+pub struct Loans {
+    pub data: Vec<Loan>,
+}
+
+#[derive(serde::Deserialize)]
+pub struct Loan {
+    #[serde(rename = "loanId")]
+    pub loan_id: i64,
+    #[serde(rename = "availableToInvest")]
+    pub available_to_invest: Money,
+    #[serde(rename = "interestRate")]
+    pub interest_rate: f32,
+    #[serde(rename = "allowedToInvest")]
+    pub allowed_to_invest: bool,
+    pub term: i32,
+    #[serde(rename = "originatorId")]
+    pub originator_id: i64,
+    #[serde(rename = "countryIso")]
+    pub country_iso: String,
+    #[serde(rename = "currencyIso")]
+    pub currency_iso: String,
+}
+
+impl From<Loan> for platform::Loan {
+    fn from(loan: Loan) -> Self {
+        platform::Loan {
+            id: loan.loan_id,
+            interest_rate: loan.interest_rate,
+            term: loan.term,
+            available_to_invest: loan.available_to_invest,
+            allowed_to_invest: loan.allowed_to_invest,
+            originator: loan.originator_id.to_string(),
+            country: loan.country_iso,
+            currency: loan.currency_iso,
+        }
+    }
+}
+
+#[derive(serde::Serialize)]
+struct InvestmentPayload {
+    amount: String,
+}
+
+fn default_base_url() -> url::Url {
+    url::Url::parse(BASE_URL).expect("BASE_URL is a valid URL")
+}
+
+fn string_to_f64<'de, D>(deserializer: D) -> Result<f64, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let s: String = serde::Deserialize::deserialize(deserializer)?;
+    s.parse::<f64>().map_err(serde::de::Error::custom)
+}
+
+async fn login(
+    client: &reqwest::Client,
+    base_url: &url::Url,
+    email: &str,
+    password: &str,
+) -> Result<String> {
+    let url = platform::endpoint(base_url, "/v1/investor/login");
+
+    let payload = LoginRequest {
+        email: email.to_string(),
+        password: password.to_string(),
+    };
+    let response = client.post(url).json(&payload).send().await?;
+
+    let login_response: LoginResponse = match platform::error::json(response).await {
+        Err(Error::Api { status, body }) if status.is_client_error() => {
+            return Err(Error::Auth(body))
+        }
+        result => result?,
+    };
+    if login_response.tfa_is_active {
+        Ok(login_response.tfa_token)
+    } else {
+        Err(Error::Auth(
+            "Two-factor authentication is not active".to_string(),
+        ))
+    }
+}
+
+async fn request_2fa(
+    base_url: &url::Url,
+    tfa_token: &str,
+    tfa_url: url::Url,
+    tfa_auth: Option<&platform::TfaAuth>,
+    tfa_tls: Option<&platform::TfaTls>,
+) -> Result<Login2faResponse> {
+    // Get the OTP from a local service
+    let totp = platform::fetch_totp(tfa_url, tfa_auth, tfa_tls).await?;
+
+    // Prepare the payload for the 2FA request
+    let payload = json!({
+        "code": totp,
+        "tfa_token": tfa_token,
+    });
+
+    // Send the 2FA request
+    let response = reqwest::Client::new()
+        .post(platform::endpoint(base_url, "/v1/investor/login/2fa"))
+        .json(&payload)
+        .send()
+        .await?;
+
+    match platform::error::json(response).await {
+        Err(Error::Auth(body)) => Err(Error::TwoFactor(body)),
+        Err(Error::Api { status, body }) if status.is_client_error() => Err(Error::TwoFactor(body)),
+        result => result,
+    }
+}
+
+async fn refresh_session(
+    client: &reqwest::Client,
+    base_url: &url::Url,
+    refresh_token: &str,
+) -> Result<Login2faResponse> {
+    let response = client
+        .post(platform::endpoint(base_url, "/v1/investor/refresh"))
+        .json(&json!({ "refresh_token": refresh_token }))
+        .send()
+        .await?;
+
+    platform::error::json(response).await
+}
+
+async fn invest_in_loan(
+    client: &reqwest::Client,
+    base_url: &url::Url,
+    access_token: &str,
+    loan_id: i64,
+    investment_amount: Money,
+) -> Result<String> {
+    // Define the endpoint URL
+    let url = platform::endpoint(base_url, &format!("/v1/loans/{}", loan_id));
+
+    // Define the payload
+    let payload = InvestmentPayload {
+        amount: investment_amount.to_string(),
+    };
+
+    info!(
+        "Investing in loan: https://peerberry.com/en/client/loan/{}",
+        loan_id
+    );
+    if env::var("DRY_RUN").is_ok() {
+        info!(
+            "DRY RUN: Would have invested {} in loan with ID {}",
+            investment_amount, loan_id
+        );
+        return Ok("DRY RUN".to_string());
+    }
+
+    // Make the POST request
+    let response = client
+        .post(url)
+        .bearer_auth(access_token)
+        .json(&payload)
+        .send()
+        .await?;
+
+    match platform::error::check(response).await {
+        Ok(response) => {
+            info!("Successfully invested in loan {}", loan_id);
+            Ok(response.text().await?)
+        }
+        Err(e) => {
+            error!("Failed to invest in loan {}: {}", loan_id, e.report());
+            Err(platform::error::classify_investment_error(e))
+        }
+    }
+}
+
+/// The Peerberry implementation of [`platform::Platform`].
+pub struct Peerberry {
+    client: reqwest::Client,
+    config: Config,
+    access_token: String,
+    /// Whether the access token is from an earlier run, refreshed or not,
+    /// so it may have been revoked since.
+    reused_session: bool,
+}
+
+impl Peerberry {
+    pub fn new(config: Config) -> Peerberry {
+        Peerberry {
+            client: reqwest::Client::new(),
+            config,
+            access_token: String::new(),
+            reused_session: false,
+        }
+    }
+
+    async fn full_login(&self) -> Result<Session> {
+        let tfa_token = login(
+            &self.client,
+            &self.config.base_url,
+            &self.config.email,
+            &self.config.password,
+        )
+        .await?;
+        let response = request_2fa(
+            &self.config.base_url,
+            &tfa_token,
+            self.config.tfa_url.clone(),
+            self.config.tfa_auth.as_ref(),
+            self.config.tfa_tls.as_ref(),
+        )
+        .await?;
+        Ok(self.store(response))
+    }
+
+    /// A session that can't be stored is still good for this run.
+    fn store(&self, response: Login2faResponse) -> Session {
+        let session = Session::new(response, &self.config.email, &self.config.base_url);
+        if let Err(e) = session.save() {
+            error!("Failed to store session: {}", e);
+        }
+        session
+    }
+
+    pub fn schedule(&self) -> platform::daemon::Schedule {
+        self.config.schedule.clone()
+    }
+
+    pub fn criteria(&self) -> platform::Criteria {
+        platform::Criteria {
+            max_term: self.config.max_loan_term,
+            min_interest_rate: self.config.min_interest,
+            limits: self.config.limits.clone(),
+            allocation: self.config.allocation.clone(),
+            currencies: self.config.currencies.clone(),
+        }
+    }
+
+    pub async fn account_info(&self, currency: &str) -> Result<AccountInfo> {
+        let response = self
+            .client
+            .get(platform::endpoint(
+                &self.config.base_url,
+                "/v2/investor/balance/main",
+            ))
+            .query(&[("currencyIso", currency)])
+            .bearer_auth(&self.access_token)
+            .send()
+            .await?;
+
+        platform::error::json(response).await
+    }
+
+    /// The active investments in `currency`.
+    pub async fn portfolio(&self, currency: &str) -> Result<Vec<portfolio::Investment>> {
+        portfolio::fetch(
+            &self.client,
+            &self.config.base_url,
+            &self.access_token,
+            currency,
+        )
+        .await
+    }
+}
+
+#[async_trait::async_trait]
+impl platform::Platform for Peerberry {
+    fn name(&self) -> &str {
+        "peerberry"
+    }
+
+    fn min_investment(&self) -> Money {
+        MIN_INVESTMENT
+    }
+
+    /// Reuses the stored session while it is valid and refreshes it when it
+    /// is about to expire. Only when that fails is there a full login.
+    async fn login(&mut self) -> Result<()> {
+        self.reused_session = true;
+        let session = match Session::load(&self.config.email, &self.config.base_url) {
+            Some(session) if session.is_fresh() => session,
+            Some(session) => {
+                match refresh_session(&self.client, &self.config.base_url, &session.refresh_token)
+                    .await
+                {
+                    Ok(response) => {
+                        info!("Refreshed access token");
+                        self.store(response)
+                    }
+                    Err(e) => {
+                        info!("{}, logging in", e.report());
+                        self.reused_session = false;
+                        self.full_login().await?
+                    }
+                }
+            }
+            None => {
+                self.reused_session = false;
+                self.full_login().await?
+            }
+        };
+        self.access_token = session.access_token;
+        Ok(())
+    }
+
+    /// A balance in another currency than asked for counts as nothing, so
+    /// it is never invested in loans of the wrong currency. A reused
+    /// session that is rejected was revoked, it is replaced by a full login.
+    async fn balance(&mut self, currency: &str) -> Result<Money> {
+        let account_info = match self.account_info(currency).await {
+            Err(Error::Auth(body)) if self.reused_session => {
+                info!("Stored session rejected ({}), logging in", body);
+                Session::clear();
+                self.reused_session = false;
+                self.access_token = self.full_login().await?.access_token;
+                self.account_info(currency).await?
+            }
+            result => result?,
+        };
+        if account_info.currency_iso != currency {
+            error!(
+                "Asked for the {} balance, got the {} balance",
+                currency, account_info.currency_iso
+            );
+            return Ok(Money::ZERO);
+        }
+        Ok(account_info.available_money)
+    }
+
+    async fn loans(&mut self, currency: &str) -> Result<Vec<platform::Loan>> {
+        let query = LoanQuery::from_config(&self.config).currency(currency);
+        let loans = LoanPages::new(
+            &self.client,
+            &self.config.base_url,
+            &self.access_token,
+            query,
+            self.config.max_loans,
+        )
+        .collect()
+        .await?;
+
+        Ok(loans.into_iter().map(platform::Loan::from).collect())
+    }
+
+    async fn holdings(&mut self, currency: &str) -> Result<Vec<platform::Holding>> {
+        let portfolio = self.portfolio(currency).await?;
+        Ok(portfolio.iter().map(platform::Holding::from).collect())
+    }
+
+    async fn invest(&mut self, loan: &platform::Loan, amount: Money) -> Result<String> {
+        invest_in_loan(
+            &self.client,
+            &self.config.base_url,
+            &self.access_token,
+            loan.id,
+            amount,
+        )
+        .await
+    }
+}
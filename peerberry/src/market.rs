@@ -0,0 +1,193 @@
+//! Querying the primary market.
+
+use platform::error::Result;
+
+use crate::{Config, Loan, Loans};
+
+/// Loans fetched per request.
+pub const PAGE_SIZE: usize = 40;
+
+/// Filters applied by Peerberry to the loans it lists, so fewer and more
+/// relevant loans come back per request.
+#[derive(Debug, Clone, Default)]
+pub struct LoanQuery {
+    min_interest_rate: Option<f32>,
+    max_interest_rate: Option<f32>,
+    min_term: Option<i32>,
+    max_term: Option<i32>,
+    countries: Vec<String>,
+    originators: Vec<i64>,
+    buyback: Option<bool>,
+    currency: Option<String>,
+}
+
+impl LoanQuery {
+    pub fn new() -> LoanQuery {
+        LoanQuery::default()
+    }
+
+    /// The query matching the criteria in the config.
+    pub fn from_config(config: &Config) -> LoanQuery {
+        let mut query = LoanQuery::new()
+            .min_interest_rate(config.min_interest)
+            .max_term(config.max_loan_term)
+            .countries(config.countries.clone())
+            .originators(config.originators.clone());
+        if config.buyback_only {
+            query = query.buyback(true);
+        }
+        query
+    }
+
+    pub fn min_interest_rate(mut self, rate: f32) -> LoanQuery {
+        self.min_interest_rate = Some(rate);
+        self
+    }
+
+    pub fn max_interest_rate(mut self, rate: f32) -> LoanQuery {
+        self.max_interest_rate = Some(rate);
+        self
+    }
+
+    /// Term in days.
+    pub fn min_term(mut self, term: i32) -> LoanQuery {
+        self.min_term = Some(term);
+        self
+    }
+
+    /// Term in days.
+    pub fn max_term(mut self, term: i32) -> LoanQuery {
+        self.max_term = Some(term);
+        self
+    }
+
+    /// ISO country codes, any country when empty.
+    pub fn countries(mut self, countries: Vec<String>) -> LoanQuery {
+        self.countries = countries;
+        self
+    }
+
+    /// Originator ids, any originator when empty.
+    pub fn originators(mut self, originators: Vec<i64>) -> LoanQuery {
+        self.originators = originators;
+        self
+    }
+
+    /// Only loans with (or without) a buyback guarantee.
+    pub fn buyback(mut self, buyback: bool) -> LoanQuery {
+        self.buyback = Some(buyback);
+        self
+    }
+
+    /// ISO code of the currency the loans are in.
+    pub fn currency(mut self, currency: &str) -> LoanQuery {
+        self.currency = Some(currency.to_string());
+        self
+    }
+
+    fn append_to(&self, url: &mut url::Url) {
+        let mut pairs = url.query_pairs_mut();
+        if let Some(rate) = self.min_interest_rate {
+            pairs.append_pair("minInterest", &rate.to_string());
+        }
+        if let Some(rate) = self.max_interest_rate {
+            pairs.append_pair("maxInterest", &rate.to_string());
+        }
+        if let Some(term) = self.min_term {
+            pairs.append_pair("minTerm", &term.to_string());
+        }
+        if let Some(term) = self.max_term {
+            pairs.append_pair("maxTerm", &term.to_string());
+        }
+        for country in &self.countries {
+            pairs.append_pair("countries[]", country);
+        }
+        for originator in &self.originators {
+            pairs.append_pair("originators[]", &originator.to_string());
+        }
+        if let Some(buyback) = self.buyback {
+            pairs.append_pair("buyback", if buyback { "1" } else { "0" });
+        }
+        if let Some(currency) = &self.currency {
+            pairs.append_pair("currencyIso", currency);
+        }
+    }
+}
+
+/// The pages of the primary market, newest loans first.
+pub struct LoanPages<'a> {
+    client: &'a reqwest::Client,
+    base_url: &'a url::Url,
+    access_token: &'a str,
+    query: LoanQuery,
+    offset: usize,
+    /// Stop after this many loans.
+    max_loans: Option<usize>,
+    done: bool,
+}
+
+impl<'a> LoanPages<'a> {
+    pub fn new(
+        client: &'a reqwest::Client,
+        base_url: &'a url::Url,
+        access_token: &'a str,
+        query: LoanQuery,
+        max_loans: Option<usize>,
+    ) -> LoanPages<'a> {
+        LoanPages {
+            client,
+            base_url,
+            access_token,
+            query,
+            offset: 0,
+            max_loans,
+            done: false,
+        }
+    }
+
+    /// The next page, `None` once the market or the cap is reached.
+    pub async fn next_page(&mut self) -> Result<Option<Vec<Loan>>> {
+        let page_size = match self.max_loans {
+            Some(max_loans) => PAGE_SIZE.min(max_loans.saturating_sub(self.offset)),
+            None => PAGE_SIZE,
+        };
+        if self.done || page_size == 0 {
+            return Ok(None);
+        }
+
+        let mut loans_url = platform::endpoint(self.base_url, "/v1/loans");
+        loans_url
+            .query_pairs_mut()
+            .append_pair("sort", "-loanId")
+            .append_pair("offset", &self.offset.to_string())
+            .append_pair("pageSize", &page_size.to_string());
+        self.query.append_to(&mut loans_url);
+
+        let response = self
+            .client
+            .get(loans_url)
+            .bearer_auth(self.access_token)
+            .send()
+            .await?;
+        let loans: Loans = platform::error::json(response).await?;
+
+        // A short page is the last one
+        self.done = loans.data.len() < page_size;
+        self.offset += loans.data.len();
+
+        if loans.data.is_empty() {
+            Ok(None)
+        } else {
+            Ok(Some(loans.data))
+        }
+    }
+
+    /// All loans on the remaining pages.
+    pub async fn collect(mut self) -> Result<Vec<Loan>> {
+        let mut loans = Vec::new();
+        while let Some(page) = self.next_page().await? {
+            loans.extend(page);
+        }
+        Ok(loans)
+    }
+}
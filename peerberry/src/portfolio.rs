@@ -0,0 +1,78 @@
+//! The investor's active investments.
+
+use platform::error::Result;
+use platform::{Holding, Money};
+
+/// Investments fetched per request.
+const PAGE_SIZE: usize = 100;
+
+#[derive(serde::Deserialize)]
+struct Investments {
+    data: Vec<Investment>,
+}
+
+#[derive(serde::Deserialize, Debug, Clone)]
+pub struct Investment {
+    #[serde(rename = "loanId")]
+    pub loan_id: i64,
+    #[serde(rename = "originatorId")]
+    pub originator_id: i64,
+    #[serde(rename = "originatorName", default)]
+    pub originator_name: String,
+    #[serde(rename = "countryIso")]
+    pub country_iso: String,
+    #[serde(rename = "currencyIso")]
+    pub currency_iso: String,
+    #[serde(rename = "principalOutstanding")]
+    pub principal_outstanding: Money,
+    #[serde(rename = "interestRate")]
+    pub interest_rate: f32,
+    /// Like `CURRENT` or `LATE`.
+    pub status: String,
+    #[serde(rename = "daysLate", default)]
+    pub days_late: u32,
+}
+
+impl From<&Investment> for Holding {
+    fn from(investment: &Investment) -> Self {
+        Holding {
+            loan_id: investment.loan_id,
+            originator: investment.originator_id.to_string(),
+            country: investment.country_iso.clone(),
+            amount: investment.principal_outstanding,
+        }
+    }
+}
+
+/// All active investments in `currency`, page by page.
+pub async fn fetch(
+    client: &reqwest::Client,
+    base_url: &url::Url,
+    access_token: &str,
+    currency: &str,
+) -> Result<Vec<Investment>> {
+    let mut investments = Vec::new();
+    loop {
+        let response = client
+            .get(platform::endpoint(
+                base_url,
+                "/v1/investor/investments/current",
+            ))
+            .query(&[
+                ("offset", investments.len().to_string()),
+                ("pageSize", PAGE_SIZE.to_string()),
+                ("currencyIso", currency.to_string()),
+            ])
+            .bearer_auth(access_token)
+            .send()
+            .await?;
+        let page: Investments = platform::error::json(response).await?;
+
+        // A short page is the last one
+        let last_page = page.data.len() < PAGE_SIZE;
+        investments.extend(page.data);
+        if last_page {
+            return Ok(investments);
+        }
+    }
+}
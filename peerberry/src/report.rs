@@ -0,0 +1,125 @@
+//! The `report` subcommand: where the money sits, per currency.
+
+use platform::{Limits, Money, Platform};
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use crate::portfolio::Investment;
+use crate::Peerberry;
+
+/// Logs in and prints the exposure in each configured currency.
+pub async fn run(peerberry: &mut Peerberry) -> anyhow::Result<()> {
+    let criteria = peerberry.criteria();
+    peerberry.login().await?;
+    for currency in &criteria.currencies {
+        let cash = peerberry.balance(currency).await?;
+        let investments = peerberry.portfolio(currency).await?;
+        print!("{}", render(currency, cash, &investments, &criteria.limits));
+    }
+    Ok(())
+}
+
+/// Breaks the investments down by originator, country and status. Shares
+/// are of the portfolio including the cash, like the limits are.
+pub fn render(currency: &str, cash: Money, investments: &[Investment], limits: &Limits) -> String {
+    let invested: Money = investments.iter().map(|i| i.principal_outstanding).sum();
+    let total = invested + cash;
+    let mut report = String::new();
+    let _ = writeln!(
+        report,
+        "{}: {} in {} loans, {} cash",
+        currency,
+        invested,
+        investments.len(),
+        cash
+    );
+
+    let originator = |i: &Investment| match i.originator_name.as_str() {
+        "" => i.originator_id.to_string(),
+        name => format!("{} ({})", name, i.originator_id),
+    };
+    section(
+        &mut report,
+        "originator",
+        breakdown(investments, originator),
+        total,
+        limits.max_originator_percent,
+    );
+    section(
+        &mut report,
+        "country",
+        breakdown(investments, |i| i.country_iso.clone()),
+        total,
+        limits.max_country_percent,
+    );
+    section(
+        &mut report,
+        "status",
+        breakdown(investments, |i| i.status.clone()),
+        total,
+        None,
+    );
+
+    let late: Vec<&Investment> = investments.iter().filter(|i| i.days_late > 0).collect();
+    if !late.is_empty() {
+        let amount: Money = late.iter().map(|i| i.principal_outstanding).sum();
+        let days = late.iter().map(|i| i.days_late).max().unwrap_or_default();
+        let _ = writeln!(
+            report,
+            "Late: {} in {} loans, up to {} days",
+            amount,
+            late.len(),
+            days
+        );
+    }
+    report
+}
+
+/// One line per key, flagging shares over `limit`.
+fn section(
+    report: &mut String,
+    name: &str,
+    rows: Vec<(String, Money)>,
+    total: Money,
+    limit: Option<f64>,
+) {
+    let _ = writeln!(report, "By {}:", name);
+    for (key, amount) in rows {
+        let share = share(amount, total);
+        let _ = write!(
+            report,
+            "  {:<24} {:>12} {:>6.1}%",
+            key,
+            amount.to_string(),
+            share
+        );
+        match limit {
+            Some(limit) if share > limit => {
+                let _ = writeln!(report, "  over the {}% limit", limit);
+            }
+            _ => report.push('\n'),
+        }
+    }
+}
+
+/// Outstanding principal per key, largest first.
+fn breakdown(
+    investments: &[Investment],
+    key: impl Fn(&Investment) -> String,
+) -> Vec<(String, Money)> {
+    let mut amounts: HashMap<String, Money> = HashMap::new();
+    for investment in investments {
+        *amounts.entry(key(investment)).or_default() += investment.principal_outstanding;
+    }
+    let mut amounts: Vec<(String, Money)> = amounts.into_iter().collect();
+    amounts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    amounts
+}
+
+fn share(amount: Money, total: Money) -> f64 {
+    if total > Money::ZERO {
+        amount.to_f64() / total.to_f64() * 100.0
+    } else {
+        0.0
+    }
+}
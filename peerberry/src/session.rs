@@ -0,0 +1,87 @@
+//! The access and refresh token pair, kept between runs so not every run
+//! needs a password and TOTP login.
+
+use anyhow::{Context, Result};
+use std::fs;
+use std::io::Write;
+use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+use xdg::BaseDirectories;
+
+use crate::Login2faResponse;
+
+/// Refresh the access token when it expires within this many seconds.
+const EXPIRY_MARGIN: u64 = 300;
+
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+pub struct Session {
+    /// The account and API the tokens are for, a session of another one
+    /// isn't used.
+    pub email: String,
+    pub base_url: String,
+    pub access_token: String,
+    pub refresh_token: String,
+    /// Unix timestamp at which the access token expires.
+    pub expires_at: u64,
+}
+
+impl Session {
+    pub fn new(response: Login2faResponse, email: &str, base_url: &url::Url) -> Session {
+        Session {
+            email: email.to_string(),
+            base_url: base_url.to_string(),
+            access_token: response.access_token,
+            refresh_token: response.refresh_token,
+            expires_at: now() + response.expires_in,
+        }
+    }
+
+    pub fn is_fresh(&self) -> bool {
+        now() + EXPIRY_MARGIN < self.expires_at
+    }
+
+    /// Reads the stored session, if there is one for `email` at
+    /// `base_url`.
+    pub fn load(email: &str, base_url: &url::Url) -> Option<Session> {
+        let content = fs::read_to_string(session_path().ok()?).ok()?;
+        let session: Session = serde_json::from_str(&content).ok()?;
+        (session.email == email && session.base_url == base_url.as_str()).then_some(session)
+    }
+
+    /// Forgets the stored session, the next login is a full one.
+    pub fn clear() {
+        if let Ok(path) = session_path() {
+            let _ = fs::remove_file(path);
+        }
+    }
+
+    /// Stores the session, readable by the current user only.
+    pub fn save(&self) -> Result<()> {
+        let path = session_path()?;
+        let mut file = fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .mode(0o600)
+            .open(&path)
+            .with_context(|| format!("Failed to open session file {:?}", path))?;
+        // The mode only applies to new files
+        file.set_permissions(fs::Permissions::from_mode(0o600))?;
+        file.write_all(serde_json::to_string(self)?.as_bytes())?;
+        Ok(())
+    }
+}
+
+fn session_path() -> Result<PathBuf> {
+    BaseDirectories::with_prefix("peerberry")?
+        .place_data_file("session.json")
+        .context("Failed to create data directory")
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
+}
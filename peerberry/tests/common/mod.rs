@@ -0,0 +1,317 @@
+//! A local stand-in for the Peerberry API, so whole runs can be tested
+//! without network access. Each endpoint answers with whatever the test
+//! scripted, and every investment is recorded.
+
+use axum::extract::{Path, Query, RawQuery, State};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::net::TcpListener;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// A canned response.
+#[derive(Clone)]
+pub struct Reply {
+    pub status: StatusCode,
+    pub body: Value,
+}
+
+impl Reply {
+    pub fn ok(body: Value) -> Reply {
+        Reply {
+            status: StatusCode::OK,
+            body,
+        }
+    }
+
+    pub fn error(status: StatusCode, message: &str) -> Reply {
+        Reply {
+            status,
+            body: json!({ "message": message }),
+        }
+    }
+}
+
+impl IntoResponse for Reply {
+    fn into_response(self) -> Response {
+        (self.status, Json(self.body)).into_response()
+    }
+}
+
+/// What the fake answers, and what it was asked.
+pub struct Script {
+    pub login: Reply,
+    pub two_factor: Reply,
+    pub refresh: Reply,
+    /// The EUR balance.
+    pub balance: Reply,
+    /// How long the balance takes to answer, to catch a run in progress.
+    pub balance_delay: Duration,
+    /// Access tokens the balance is refused for.
+    pub revoked_tokens: Vec<String>,
+    /// Balances in other currencies, by ISO code.
+    pub other_balances: HashMap<String, Reply>,
+    /// The whole market, newest loan first, paged like Peerberry does.
+    pub loans: Vec<Value>,
+    /// Answers to investments in these loans, success for any other loan.
+    pub invest: HashMap<i64, Reply>,
+    /// The active investments.
+    pub portfolio: Vec<Value>,
+    /// Only hand out codes to requests with this `Authorization` header
+    /// prefix, to anyone when `None`.
+    pub totp_authorization: Option<String>,
+
+    pub logins: usize,
+    pub refreshes: usize,
+    pub balance_requests: usize,
+    pub portfolio_requests: usize,
+    /// The query string of each `/v1/loans` request.
+    pub loan_queries: Vec<String>,
+    /// Loan id and amount of each successful investment.
+    pub investments: Vec<(i64, String)>,
+    /// The `Authorization` header of each request for a code.
+    pub totp_requests: Vec<Option<String>>,
+}
+
+impl Default for Script {
+    fn default() -> Self {
+        Script {
+            login: Reply::ok(json!({ "tfa_is_active": true, "tfa_token": "tfa-token" })),
+            two_factor: Reply::ok(json!({
+                "access_token": "access-token",
+                "expires_in": 3600,
+                "refresh_token": "refresh-token",
+                "status": "ok",
+            })),
+            refresh: Reply::ok(json!({
+                "access_token": "refreshed-token",
+                "expires_in": 3600,
+                "refresh_token": "refresh-token",
+                "status": "ok",
+            })),
+            balance: balance("100.00"),
+            balance_delay: Duration::ZERO,
+            revoked_tokens: Vec::new(),
+            other_balances: HashMap::new(),
+            loans: Vec::new(),
+            invest: HashMap::new(),
+            portfolio: Vec::new(),
+            totp_authorization: None,
+            logins: 0,
+            refreshes: 0,
+            balance_requests: 0,
+            portfolio_requests: 0,
+            loan_queries: Vec::new(),
+            investments: Vec::new(),
+            totp_requests: Vec::new(),
+        }
+    }
+}
+
+type Shared = Arc<Mutex<Script>>;
+
+pub fn balance(available_money: &str) -> Reply {
+    balance_in("EUR", available_money)
+}
+
+pub fn balance_in(currency: &str, available_money: &str) -> Reply {
+    Reply::ok(json!({
+        "currencyIso": currency,
+        "availableMoney": available_money,
+        "invested": "0.00",
+        "totalProfit": "0.00",
+        "totalBalance": available_money,
+        "balanceGrowth": "0.00",
+        "balanceGrowthAmount": "0.00",
+    }))
+}
+
+pub fn loan(id: i64, interest_rate: f32, term: i32) -> Value {
+    loan_in("EUR", id, interest_rate, term)
+}
+
+pub fn loan_in(currency: &str, id: i64, interest_rate: f32, term: i32) -> Value {
+    json!({
+        "loanId": id,
+        "availableToInvest": 1000.0,
+        "interestRate": interest_rate,
+        "allowedToInvest": true,
+        "term": term,
+        "originatorId": id % 3,
+        "countryIso": "LV",
+        "currencyIso": currency,
+    })
+}
+
+pub fn investment(loan_id: i64, country: &str, outstanding: f64, days_late: u32) -> Value {
+    json!({
+        "loanId": loan_id,
+        "originatorId": loan_id % 3,
+        "originatorName": format!("Originator {}", loan_id % 3),
+        "countryIso": country,
+        "currencyIso": "EUR",
+        "principalOutstanding": format!("{:.2}", outstanding),
+        "interestRate": 12.0,
+        "status": if days_late > 0 { "LATE" } else { "CURRENT" },
+        "daysLate": days_late,
+    })
+}
+
+pub struct MockPeerberry {
+    pub url: String,
+    pub script: Shared,
+}
+
+impl MockPeerberry {
+    /// Starts the fake on a free local port.
+    pub fn start(script: Script) -> MockPeerberry {
+        let script = Arc::new(Mutex::new(script));
+        let app = Router::new()
+            .route("/v1/investor/login", post(login))
+            .route("/v1/investor/login/2fa", post(two_factor))
+            .route("/v1/investor/refresh", post(refresh))
+            .route("/v2/investor/balance/main", get(balance_main))
+            .route("/v1/investor/investments/current", get(investments))
+            .route("/v1/loans", get(loans))
+            .route("/v1/loans/:id", post(invest))
+            .route("/totp", get(totp))
+            .with_state(script.clone());
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let server = axum::Server::from_tcp(listener)
+            .unwrap()
+            .serve(app.into_make_service());
+        tokio::spawn(server);
+
+        MockPeerberry { url, script }
+    }
+
+    pub fn script(&self) -> std::sync::MutexGuard<'_, Script> {
+        self.script.lock().unwrap()
+    }
+}
+
+async fn login(State(script): State<Shared>) -> Reply {
+    let mut script = script.lock().unwrap();
+    script.logins += 1;
+    script.login.clone()
+}
+
+async fn two_factor(State(script): State<Shared>, Json(payload): Json<Value>) -> Reply {
+    if payload["code"] != "123456" {
+        return Reply::error(StatusCode::BAD_REQUEST, "Invalid code");
+    }
+    script.lock().unwrap().two_factor.clone()
+}
+
+async fn refresh(State(script): State<Shared>) -> Reply {
+    let mut script = script.lock().unwrap();
+    script.refreshes += 1;
+    script.refresh.clone()
+}
+
+async fn balance_main(
+    State(script): State<Shared>,
+    Query(params): Query<HashMap<String, String>>,
+    headers: HeaderMap,
+) -> Reply {
+    let delay = {
+        let mut script = script.lock().unwrap();
+        script.balance_requests += 1;
+        script.balance_delay
+    };
+    tokio::time::sleep(delay).await;
+    let script = script.lock().unwrap();
+    let token = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    if token.is_some_and(|token| script.revoked_tokens.iter().any(|revoked| revoked == token)) {
+        return Reply::error(StatusCode::UNAUTHORIZED, "Token revoked");
+    }
+    match params.get("currencyIso").map(String::as_str) {
+        None | Some("EUR") => script.balance.clone(),
+        Some(currency) => script
+            .other_balances
+            .get(currency)
+            .cloned()
+            .unwrap_or_else(|| Reply::error(StatusCode::NOT_FOUND, "No such currency")),
+    }
+}
+
+async fn loans(
+    State(script): State<Shared>,
+    Query(params): Query<HashMap<String, String>>,
+    RawQuery(query): RawQuery,
+) -> Reply {
+    let mut script = script.lock().unwrap();
+    script.loan_queries.push(query.unwrap_or_default());
+
+    let offset: usize = params["offset"].parse().unwrap();
+    let page_size: usize = params["pageSize"].parse().unwrap();
+    let currency = params.get("currencyIso");
+    let page: Vec<Value> = script
+        .loans
+        .iter()
+        .filter(|loan| currency.is_none_or(|currency| loan["currencyIso"] == **currency))
+        .skip(offset)
+        .take(page_size)
+        .cloned()
+        .collect();
+    Reply::ok(json!({ "data": page }))
+}
+
+async fn investments(
+    State(script): State<Shared>,
+    Query(params): Query<HashMap<String, String>>,
+) -> Reply {
+    let mut script = script.lock().unwrap();
+    script.portfolio_requests += 1;
+    let offset: usize = params["offset"].parse().unwrap();
+    let page_size: usize = params["pageSize"].parse().unwrap();
+    let page: Vec<Value> = script
+        .portfolio
+        .iter()
+        .filter(|investment| investment["currencyIso"] == params["currencyIso"].as_str())
+        .skip(offset)
+        .take(page_size)
+        .cloned()
+        .collect();
+    Reply::ok(json!({ "data": page }))
+}
+
+async fn invest(
+    State(script): State<Shared>,
+    Path(id): Path<i64>,
+    Json(payload): Json<Value>,
+) -> Reply {
+    let mut script = script.lock().unwrap();
+    match script.invest.get(&id) {
+        Some(reply) => reply.clone(),
+        None => {
+            let amount = payload["amount"].as_str().unwrap().to_string();
+            script.investments.push((id, amount));
+            Reply::ok(json!({ "status": "ok" }))
+        }
+    }
+}
+
+async fn totp(State(script): State<Shared>, headers: HeaderMap) -> Reply {
+    let mut script = script.lock().unwrap();
+    let authorization = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string);
+    script.totp_requests.push(authorization.clone());
+    match (&script.totp_authorization, authorization) {
+        (Some(prefix), Some(authorization)) if authorization.starts_with(prefix) => {}
+        (Some(_), _) => return Reply::error(StatusCode::UNAUTHORIZED, "Missing or invalid key"),
+        (None, _) => {}
+    }
+    Reply::ok(json!({ "totp": "123456" }))
+}
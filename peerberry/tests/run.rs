@@ -0,0 +1,490 @@
+//! End-to-end runs of the `peerberry` binary against [`MockPeerberry`].
+
+mod common;
+
+use axum::http::StatusCode;
+use common::{balance, balance_in, investment, loan, loan_in, MockPeerberry, Reply, Script};
+use platform::Ledger;
+use std::fs;
+use std::process::Output;
+use std::time::Duration;
+use tempfile::TempDir;
+use tokio::process::Command;
+
+struct Run {
+    mock: MockPeerberry,
+    dir: TempDir,
+}
+
+impl Run {
+    fn new(script: Script) -> Run {
+        Run::with_config(script, "")
+    }
+
+    /// `extra` is appended to the config.
+    fn with_config(script: Script, extra: &str) -> Run {
+        let mock = MockPeerberry::start(script);
+        let dir = tempfile::tempdir().unwrap();
+        let config = format!(
+            "email = \"investor@example.com\"\n\
+             password = \"secret\"\n\
+             max_loan_term = 60\n\
+             min_interest = 10.0\n\
+             tfa_url = \"{url}/totp\"\n\
+             base_url = \"{url}\"\n\
+             max_per_loan = 20.0\n\
+             {extra}\n",
+            url = mock.url,
+        );
+        fs::write(dir.path().join("config.toml"), config).unwrap();
+        Run { mock, dir }
+    }
+
+    async fn run(&self) -> Output {
+        self.run_with(&[]).await
+    }
+
+    async fn run_with(&self, args: &[&str]) -> Output {
+        self.command(args).output().await.unwrap()
+    }
+
+    fn command(&self, args: &[&str]) -> Command {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_peerberry"));
+        command
+            .args(args)
+            .current_dir(self.dir.path())
+            .env("XDG_DATA_HOME", self.dir.path().join("data"))
+            .env("XDG_CONFIG_HOME", self.dir.path().join("config"))
+            .env_remove("DRY_RUN")
+            .kill_on_drop(true);
+        command
+    }
+
+    fn session_path(&self) -> std::path::PathBuf {
+        self.dir.path().join("data/peerberry/session.json")
+    }
+
+    fn ledger(&self) -> Ledger {
+        Ledger::open(&self.dir.path().join("data/peerberry/ledger.sqlite")).unwrap()
+    }
+}
+
+fn amounts(run: &Run) -> Vec<(i64, String)> {
+    run.mock.script().investments.clone()
+}
+
+#[tokio::test]
+async fn invests_in_desirable_loans() {
+    let run = Run::new(Script {
+        loans: vec![loan(3, 12.0, 30), loan(2, 8.0, 30), loan(1, 11.0, 90)],
+        ..Script::default()
+    });
+
+    let output = run.run().await;
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(amounts(&run), vec![(3, "20.00".to_string())]);
+
+    let ledger = run.ledger();
+    assert!(ledger.holds("peerberry", 3).unwrap());
+    assert!(!ledger.holds("peerberry", 2).unwrap());
+}
+
+#[tokio::test]
+async fn does_not_invest_twice() {
+    let run = Run::new(Script {
+        loans: vec![loan(1, 12.0, 30)],
+        ..Script::default()
+    });
+
+    assert!(run.run().await.status.success());
+    assert!(run.run().await.status.success());
+    assert_eq!(amounts(&run).len(), 1);
+}
+
+#[tokio::test]
+async fn reuses_the_session() {
+    let run = Run::new(Script::default());
+
+    assert!(run.run().await.status.success());
+    assert!(run.run().await.status.success());
+    assert_eq!(run.mock.script().logins, 1);
+    assert_eq!(run.mock.script().refreshes, 0);
+}
+
+/// A 2FA login whose access token expires within the refresh margin.
+fn expiring_login() -> Reply {
+    Reply::ok(serde_json::json!({
+        "access_token": "access-token",
+        "expires_in": 60,
+        "refresh_token": "refresh-token",
+        "status": "ok",
+    }))
+}
+
+#[tokio::test]
+async fn refreshes_an_expiring_session() {
+    let run = Run::new(Script {
+        two_factor: expiring_login(),
+        ..Script::default()
+    });
+
+    assert!(run.run().await.status.success());
+    assert!(run.run().await.status.success());
+    assert_eq!(run.mock.script().logins, 1);
+    assert_eq!(run.mock.script().refreshes, 1);
+    let session = fs::read_to_string(run.session_path()).unwrap();
+    assert!(session.contains("refreshed-token"), "{}", session);
+}
+
+#[tokio::test]
+async fn logs_in_again_when_refreshing_fails() {
+    let run = Run::new(Script {
+        two_factor: expiring_login(),
+        refresh: Reply::error(StatusCode::UNAUTHORIZED, "Invalid refresh token"),
+        ..Script::default()
+    });
+
+    assert!(run.run().await.status.success());
+    let output = run.run().await;
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(run.mock.script().refreshes, 1);
+    assert_eq!(run.mock.script().logins, 2);
+}
+
+#[tokio::test]
+async fn logs_in_again_when_the_session_was_revoked() {
+    let run = Run::new(Script::default());
+    assert!(run.run().await.status.success());
+    assert!(run.session_path().exists());
+
+    {
+        let mut script = run.mock.script();
+        script.revoked_tokens = vec!["access-token".to_string()];
+        script.two_factor.body["access_token"] = "new-token".into();
+    }
+    assert!(run.run().await.status.success());
+    assert_eq!(run.mock.script().logins, 2);
+    let session = fs::read_to_string(run.session_path()).unwrap();
+    assert!(session.contains("new-token"), "{}", session);
+}
+
+#[tokio::test]
+async fn ignores_the_session_of_another_account() {
+    let run = Run::new(Script::default());
+    assert!(run.run().await.status.success());
+
+    let config_path = run.dir.path().join("config.toml");
+    let config = fs::read_to_string(&config_path).unwrap();
+    fs::write(
+        &config_path,
+        config.replace("investor@example.com", "other@example.com"),
+    )
+    .unwrap();
+    assert!(run.run().await.status.success());
+    assert_eq!(run.mock.script().logins, 2);
+
+    // The session is now the other account's
+    assert!(run.run().await.status.success());
+    assert_eq!(run.mock.script().logins, 2);
+}
+
+#[tokio::test]
+async fn rejected_login_exits_with_2() {
+    let run = Run::new(Script {
+        login: Reply::error(StatusCode::BAD_REQUEST, "Invalid credentials"),
+        ..Script::default()
+    });
+
+    assert_eq!(run.run().await.status.code(), Some(2));
+}
+
+#[tokio::test]
+async fn rejected_2fa_code_exits_with_3() {
+    let run = Run::new(Script {
+        two_factor: Reply::error(StatusCode::UNPROCESSABLE_ENTITY, "Invalid code"),
+        ..Script::default()
+    });
+
+    assert_eq!(run.run().await.status.code(), Some(3));
+}
+
+#[tokio::test]
+async fn unexpected_balance_exits_with_7() {
+    let run = Run::new(Script {
+        balance: Reply::ok(serde_json::json!({ "unexpected": true })),
+        ..Script::default()
+    });
+
+    assert_eq!(run.run().await.status.code(), Some(7));
+}
+
+#[tokio::test]
+async fn server_error_exits_with_8() {
+    let run = Run::new(Script {
+        balance: Reply::error(StatusCode::INTERNAL_SERVER_ERROR, "Oops"),
+        ..Script::default()
+    });
+
+    assert_eq!(run.run().await.status.code(), Some(8));
+}
+
+#[tokio::test]
+async fn skips_fully_funded_loans() {
+    let mut script = Script {
+        loans: vec![loan(2, 12.0, 30), loan(1, 12.0, 30)],
+        ..Script::default()
+    };
+    script.invest.insert(
+        2,
+        Reply::error(StatusCode::BAD_REQUEST, "Loan is fully funded"),
+    );
+    let run = Run::new(script);
+
+    assert!(run.run().await.status.success());
+    assert_eq!(amounts(&run), vec![(1, "20.00".to_string())]);
+    assert!(!run.ledger().holds("peerberry", 2).unwrap());
+}
+
+#[tokio::test]
+async fn stops_when_funds_run_out() {
+    let mut script = Script {
+        loans: vec![loan(3, 12.0, 30), loan(2, 12.0, 30), loan(1, 12.0, 30)],
+        ..Script::default()
+    };
+    script.invest.insert(
+        3,
+        Reply::error(StatusCode::BAD_REQUEST, "Insufficient funds"),
+    );
+    let run = Run::new(script);
+
+    assert!(run.run().await.status.success());
+    assert!(amounts(&run).is_empty());
+}
+
+#[tokio::test]
+async fn low_balance_invests_nothing() {
+    let run = Run::new(Script {
+        balance: balance("5.00"),
+        loans: vec![loan(1, 12.0, 30)],
+        ..Script::default()
+    });
+
+    assert!(run.run().await.status.success());
+    assert!(run.mock.script().loan_queries.is_empty());
+    assert!(amounts(&run).is_empty());
+}
+
+#[tokio::test]
+async fn pages_through_the_market() {
+    let run = Run::new(Script {
+        balance: balance("1000.00"),
+        loans: (1..=100).rev().map(|id| loan(id, 8.0, 30)).collect(),
+        ..Script::default()
+    });
+
+    assert!(run.run().await.status.success());
+    let queries = run.mock.script().loan_queries.clone();
+    assert_eq!(queries.len(), 3);
+    assert!(queries[2].contains("offset=80"));
+    assert!(queries[0].contains("minInterest=10"));
+    assert!(queries[0].contains("maxTerm=60"));
+}
+
+#[tokio::test]
+async fn stops_paging_at_max_loans() {
+    let run = Run::with_config(
+        Script {
+            balance: balance("1000.00"),
+            loans: (1..=100).rev().map(|id| loan(id, 8.0, 30)).collect(),
+            ..Script::default()
+        },
+        "max_loans = 50",
+    );
+
+    assert!(run.run().await.status.success());
+    let queries = run.mock.script().loan_queries.clone();
+    assert_eq!(queries.len(), 2);
+    assert!(queries[1].contains("pageSize=10"));
+}
+
+#[tokio::test]
+async fn invests_each_currency_in_its_own_loans() {
+    let mut script = Script {
+        balance: balance("15.00"),
+        loans: vec![loan_in("PLN", 3, 12.0, 30), loan(2, 12.0, 30)],
+        ..Script::default()
+    };
+    script
+        .other_balances
+        .insert("PLN".to_string(), balance_in("PLN", "50.00"));
+    let run = Run::with_config(script, "currencies = [\"EUR\", \"PLN\"]");
+
+    assert!(run.run().await.status.success());
+    assert_eq!(
+        amounts(&run),
+        vec![(2, "15.00".to_string()), (3, "20.00".to_string())]
+    );
+}
+
+#[tokio::test]
+async fn ignores_a_balance_in_the_wrong_currency() {
+    let mut script = Script {
+        loans: vec![loan_in("PLN", 1, 12.0, 30)],
+        ..Script::default()
+    };
+    script
+        .other_balances
+        .insert("PLN".to_string(), balance("50.00"));
+    let run = Run::with_config(script, "currencies = [\"PLN\"]");
+
+    assert!(run.run().await.status.success());
+    assert!(amounts(&run).is_empty());
+}
+
+#[tokio::test]
+async fn holdings_count_towards_the_limits() {
+    // Originator 0 already holds half of the 200 portfolio.
+    let run = Run::with_config(
+        Script {
+            portfolio: vec![investment(6, "LV", 100.0, 0)],
+            loans: vec![loan(3, 12.0, 30), loan(4, 12.0, 30)],
+            ..Script::default()
+        },
+        "max_originator_percent = 50.0",
+    );
+
+    let output = run.run().await;
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(amounts(&run), vec![(4, "20.00".to_string())]);
+}
+
+#[tokio::test]
+async fn fetches_holdings_only_for_the_limits() {
+    let run = Run::new(Script {
+        loans: vec![loan(3, 12.0, 30)],
+        ..Script::default()
+    });
+
+    let output = run.run().await;
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(amounts(&run), vec![(3, "20.00".to_string())]);
+    assert_eq!(run.mock.script().portfolio_requests, 0);
+}
+
+#[tokio::test]
+async fn reports_the_exposure() {
+    let run = Run::with_config(
+        Script {
+            balance: balance("50.00"),
+            portfolio: vec![
+                investment(1, "LV", 100.0, 0),
+                investment(2, "PL", 25.0, 0),
+                investment(4, "LV", 25.0, 12),
+            ],
+            loans: vec![loan(3, 12.0, 30)],
+            ..Script::default()
+        },
+        "max_originator_percent = 50.0",
+    );
+
+    let output = run.run_with(&["report"]).await;
+    assert!(output.status.success(), "{:?}", output);
+    let report = String::from_utf8(output.stdout).unwrap();
+    assert!(
+        report.contains("EUR: 150.00 in 3 loans, 50.00 cash"),
+        "{}",
+        report
+    );
+    assert!(report.contains("Originator 1 (1)"), "{}", report);
+    assert!(report.contains("62.5%  over the 50% limit"), "{}", report);
+    assert!(
+        report.contains("Late: 25.00 in 1 loans, up to 12 days"),
+        "{}",
+        report
+    );
+    assert!(amounts(&run).is_empty());
+}
+
+#[tokio::test]
+async fn signs_the_request_for_a_code() {
+    let run = Run::with_config(
+        Script {
+            totp_authorization: Some("HMAC peerberry:".to_string()),
+            ..Script::default()
+        },
+        "[tfa_auth]\nclient = \"peerberry\"\nkey = \"tfa-key\"",
+    );
+
+    let output = run.run().await;
+    assert!(output.status.success(), "{:?}", output);
+
+    let authorization = run.mock.script().totp_requests[0].clone().unwrap();
+    let timestamp = authorization.split(':').nth(1).unwrap().parse().unwrap();
+    let auth = platform::TfaAuth {
+        client: "peerberry".to_string(),
+        key: "tfa-key".to_string(),
+    };
+    assert_eq!(authorization, auth.authorization("/totp", timestamp));
+}
+
+#[tokio::test]
+async fn refused_code_exits_with_3() {
+    let run = Run::new(Script {
+        totp_authorization: Some("HMAC peerberry:".to_string()),
+        ..Script::default()
+    });
+
+    let output = run.run().await;
+    assert_eq!(output.status.code(), Some(3), "{:?}", output);
+    assert_eq!(run.mock.script().totp_requests, vec![None]);
+}
+
+/// Polls every second, so the daemon tests don't take long.
+const FAST_POLLS: &str = "poll_interval = 1\npoll_jitter = 0";
+
+#[tokio::test]
+async fn daemon_stops_after_two_rejected_logins() {
+    let run = Run::with_config(
+        Script {
+            login: Reply::error(StatusCode::BAD_REQUEST, "Invalid credentials"),
+            ..Script::default()
+        },
+        FAST_POLLS,
+    );
+
+    let output = tokio::time::timeout(Duration::from_secs(20), run.run_with(&["--daemon"]))
+        .await
+        .expect("the daemon kept retrying");
+    assert_eq!(output.status.code(), Some(2), "{:?}", output);
+    assert_eq!(run.mock.script().logins, 2);
+}
+
+#[tokio::test]
+async fn daemon_finishes_the_current_run_on_sigterm() {
+    let run = Run::with_config(
+        Script {
+            balance_delay: Duration::from_secs(1),
+            loans: vec![loan(1, 12.0, 30)],
+            ..Script::default()
+        },
+        FAST_POLLS,
+    );
+    let mut daemon = run.command(&["--daemon"]).spawn().unwrap();
+
+    // Wait until the run is waiting for the balance.
+    while run.mock.script().balance_requests == 0 {
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    let pid = daemon.id().unwrap().to_string();
+    let kill = Command::new("kill").args(["-TERM", &pid]).status();
+    assert!(kill.await.unwrap().success());
+
+    let status = tokio::time::timeout(Duration::from_secs(20), daemon.wait())
+        .await
+        .expect("the daemon didn't stop")
+        .unwrap();
+    assert!(status.success(), "{:?}", status);
+    // The run was finished, and no other one started.
+    assert_eq!(amounts(&run), vec![(1, "20.00".to_string())]);
+    assert_eq!(run.mock.script().balance_requests, 1);
+}
@@ -0,0 +1,39 @@
+use crate::Money;
+
+/// How the balance is spread over loans.
+#[derive(serde::Deserialize, Debug, Default, Clone)]
+pub struct Allocation {
+    /// The most to put in a single loan in one run.
+    pub max_per_loan: Option<Money>,
+    /// The least to put in a loan, raised to the platform minimum if lower.
+    #[serde(default)]
+    pub min_per_loan: Money,
+    /// Cash that is never invested.
+    #[serde(default)]
+    pub cash_reserve: Money,
+}
+
+impl Allocation {
+    /// The part of `balance` that may be invested.
+    pub fn investable(&self, balance: Money) -> Money {
+        (balance - self.cash_reserve).max(Money::ZERO)
+    }
+
+    /// The amount to put in a loan that can take at most `upper_bound`, or
+    /// `None` when that would be less than the minimum.
+    pub fn amount(&self, upper_bound: Money, platform_minimum: Money) -> Option<Money> {
+        let amount = match self.max_per_loan {
+            Some(max) => upper_bound.min(max),
+            None => upper_bound,
+        };
+        if amount >= self.minimum(platform_minimum) {
+            Some(amount)
+        } else {
+            None
+        }
+    }
+
+    pub fn minimum(&self, platform_minimum: Money) -> Money {
+        self.min_per_loan.max(platform_minimum)
+    }
+}
@@ -0,0 +1,63 @@
+use anyhow::{Context, Result};
+use std::fs;
+use url::Url;
+use xdg::BaseDirectories;
+
+pub fn deserialize_url<'de, D>(deserializer: D) -> Result<Url, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let s: String = serde::Deserialize::deserialize(deserializer)?;
+    let url = Url::parse(&s).map_err(serde::de::Error::custom)?;
+    if url.cannot_be_a_base() {
+        return Err(serde::de::Error::custom(format!(
+            "{} isn't a web address",
+            s
+        )));
+    }
+    Ok(url)
+}
+
+/// `path`, like `/v1/loans`, under `base_url`, whether or not that ends in
+/// a slash. `base_url` comes from [`deserialize_url`].
+pub fn endpoint(base_url: &Url, path: &str) -> Url {
+    let mut url = base_url.clone();
+    if let Ok(mut segments) = url.path_segments_mut() {
+        segments
+            .pop_if_empty()
+            .extend(path.split('/').filter(|segment| !segment.is_empty()));
+    }
+    url
+}
+
+/// Currencies invested in when the config doesn't list any.
+pub fn default_currencies() -> Vec<String> {
+    vec!["EUR".to_string()]
+}
+
+/// Reads `config.toml` from the current directory, falling back to the XDG
+/// config directory of `app_name`.
+pub fn read_config<T: serde::de::DeserializeOwned>(app_name: &str) -> Result<T> {
+    let content = read_config_file(app_name)?;
+    toml::from_str(&content).context("Failed to parse config file")
+}
+
+fn read_config_file(app_name: &str) -> Result<String> {
+    // Try reading from current directory
+    if let Ok(content) = fs::read_to_string("config.toml") {
+        return Ok(content);
+    }
+
+    // If failed, try reading from XDG config directory
+    if let Some(config_path) = xdg_config_path(app_name, "config.toml") {
+        fs::read_to_string(&config_path)
+            .with_context(|| format!("Failed to read config file at {:?}", config_path))
+    } else {
+        Err(anyhow::anyhow!("Config file not found"))
+    }
+}
+
+fn xdg_config_path(app_name: &str, file_name: &str) -> Option<std::path::PathBuf> {
+    let xdg_dirs = BaseDirectories::with_prefix(app_name).ok()?;
+    xdg_dirs.find_config_file(file_name)
+}
@@ -0,0 +1,89 @@
+//! Running the bot continuously instead of once per timer tick.
+
+use anyhow::Result;
+use log::{error, info};
+use rand::Rng;
+use std::time::Duration;
+use tokio::signal::unix::{signal, SignalKind};
+
+use crate::{Criteria, Error, Ledger, Platform};
+
+fn default_poll_interval() -> u64 {
+    60
+}
+
+fn default_poll_jitter() -> u64 {
+    15
+}
+
+/// How often the market is polled, in seconds.
+#[derive(serde::Deserialize, Debug, Clone)]
+pub struct Schedule {
+    #[serde(default = "default_poll_interval")]
+    pub poll_interval: u64,
+    /// Up to this many seconds are added to or taken off each interval, so
+    /// polls don't happen like clockwork.
+    #[serde(default = "default_poll_jitter")]
+    pub poll_jitter: u64,
+}
+
+impl Default for Schedule {
+    fn default() -> Self {
+        Schedule {
+            poll_interval: default_poll_interval(),
+            poll_jitter: default_poll_jitter(),
+        }
+    }
+}
+
+impl Schedule {
+    fn next_delay(&self) -> Duration {
+        let jitter = self.poll_jitter.min(self.poll_interval) as i64;
+        let offset = rand::thread_rng().gen_range(-jitter..=jitter);
+        Duration::from_secs((self.poll_interval as i64 + offset).max(1) as u64)
+    }
+}
+
+/// Calls [`crate::run`] on the schedule until SIGTERM or SIGINT. A run in
+/// progress is always finished. Failed runs are logged and retried on the
+/// next poll. Only a rejected 2FA code, or being rejected on two polls in a
+/// row, ends the daemon: retrying bad credentials risks getting locked out.
+pub async fn run<P: Platform + Send>(
+    platform: &mut P,
+    criteria: &Criteria,
+    ledger: &mut Ledger,
+    schedule: &Schedule,
+) -> Result<()> {
+    let mut terminate = signal(SignalKind::terminate())?;
+    let mut interrupt = signal(SignalKind::interrupt())?;
+    let mut rejected = false;
+
+    loop {
+        let mut delay = schedule.next_delay();
+        match crate::run(platform, criteria, ledger).await {
+            Ok(()) => rejected = false,
+            Err(e) => {
+                match e.downcast_ref::<Error>() {
+                    Some(Error::TwoFactor(_)) => return Err(e),
+                    Some(Error::Auth(_)) if rejected => return Err(e),
+                    Some(Error::Auth(_)) => rejected = true,
+                    Some(Error::RateLimited {
+                        retry_after: Some(retry_after),
+                    }) => delay = delay.max(*retry_after),
+                    _ => {}
+                }
+                error!("Run failed: {:#}", e);
+            }
+        }
+
+        info!("Next poll in {} seconds", delay.as_secs());
+        tokio::select! {
+            _ = tokio::time::sleep(delay) => {}
+            _ = terminate.recv() => break,
+            _ = interrupt.recv() => break,
+        }
+    }
+
+    info!("Shutting down");
+    Ok(())
+}
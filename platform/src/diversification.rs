@@ -0,0 +1,179 @@
+use std::collections::HashMap;
+
+use crate::Money;
+
+/// Concentration limits, as a percentage of the portfolio. The portfolio is
+/// what is currently invested plus the cash about to be invested. A missing
+/// limit isn't enforced.
+#[derive(serde::Deserialize, Debug, Default, Clone)]
+pub struct Limits {
+    pub max_originator_percent: Option<f64>,
+    pub max_country_percent: Option<f64>,
+    pub max_loan_percent: Option<f64>,
+}
+
+impl Limits {
+    /// Whether any limit is set, only then do the holdings matter.
+    pub fn any(&self) -> bool {
+        self.max_originator_percent.is_some()
+            || self.max_country_percent.is_some()
+            || self.max_loan_percent.is_some()
+    }
+}
+
+/// Outstanding principal in a single loan.
+#[derive(Debug, Clone)]
+pub struct Holding {
+    pub loan_id: i64,
+    pub originator: String,
+    pub country: String,
+    pub amount: Money,
+}
+
+/// How much of the portfolio sits with each originator, country and loan.
+#[derive(Debug, Default)]
+pub struct Exposure {
+    total: Money,
+    by_originator: HashMap<String, Money>,
+    by_country: HashMap<String, Money>,
+    by_loan: HashMap<i64, Money>,
+}
+
+impl Exposure {
+    /// `cash` is the money that is going to be invested, it counts towards
+    /// the size of the portfolio.
+    pub fn new(holdings: &[Holding], cash: Money) -> Exposure {
+        let mut exposure = Exposure {
+            total: cash,
+            ..Default::default()
+        };
+        for holding in holdings {
+            exposure.total += holding.amount;
+            exposure.add(holding);
+        }
+        exposure
+    }
+
+    /// Records a new investment. The money came out of the cash that was
+    /// already counted, so the size of the portfolio stays the same.
+    pub fn add(&mut self, holding: &Holding) {
+        *self
+            .by_originator
+            .entry(holding.originator.clone())
+            .or_default() += holding.amount;
+        *self.by_country.entry(holding.country.clone()).or_default() += holding.amount;
+        *self.by_loan.entry(holding.loan_id).or_default() += holding.amount;
+    }
+
+    /// The most that can go into `loan_id` without breaking any of the
+    /// limits, [`Money::MAX`] when there are none.
+    pub fn headroom(
+        &self,
+        limits: &Limits,
+        loan_id: i64,
+        originator: &str,
+        country: &str,
+    ) -> Money {
+        let room = |limit: Option<f64>, current: Option<&Money>| match limit {
+            Some(percent) => (self.total.percent(percent) - current.copied().unwrap_or_default())
+                .max(Money::ZERO),
+            None => Money::MAX,
+        };
+
+        room(
+            limits.max_originator_percent,
+            self.by_originator.get(originator),
+        )
+        .min(room(
+            limits.max_country_percent,
+            self.by_country.get(country),
+        ))
+        .min(room(limits.max_loan_percent, self.by_loan.get(&loan_id)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn euros(amount: i64) -> Money {
+        Money::from_cents(amount * 100)
+    }
+
+    fn holding(loan_id: i64, originator: &str, country: &str, amount: i64) -> Holding {
+        Holding {
+            loan_id,
+            originator: originator.to_string(),
+            country: country.to_string(),
+            amount: euros(amount),
+        }
+    }
+
+    fn limits(originator: Option<f64>, country: Option<f64>, loan: Option<f64>) -> Limits {
+        Limits {
+            max_originator_percent: originator,
+            max_country_percent: country,
+            max_loan_percent: loan,
+        }
+    }
+
+    #[test]
+    fn missing_limits_leave_unlimited_room() {
+        let exposure = Exposure::new(&[holding(1, "A", "LV", 90)], euros(10));
+        let limits = Limits::default();
+        assert!(!limits.any());
+        assert_eq!(exposure.headroom(&limits, 1, "A", "LV"), Money::MAX);
+    }
+
+    #[test]
+    fn cash_counts_towards_the_portfolio() {
+        let exposure = Exposure::new(&[], euros(100));
+        let limits = limits(Some(25.0), None, None);
+        assert_eq!(exposure.headroom(&limits, 1, "A", "LV"), euros(25));
+    }
+
+    #[test]
+    fn limits_each_originator() {
+        let exposure = Exposure::new(&[holding(1, "A", "LV", 30)], euros(70));
+        let limits = limits(Some(50.0), None, None);
+        assert_eq!(exposure.headroom(&limits, 2, "A", "LV"), euros(20));
+        assert_eq!(exposure.headroom(&limits, 2, "B", "LV"), euros(50));
+    }
+
+    #[test]
+    fn limits_each_country() {
+        let exposure = Exposure::new(&[holding(1, "A", "LV", 40)], euros(60));
+        let limits = limits(None, Some(50.0), None);
+        assert_eq!(exposure.headroom(&limits, 2, "B", "LV"), euros(10));
+        assert_eq!(exposure.headroom(&limits, 2, "B", "EE"), euros(50));
+    }
+
+    #[test]
+    fn limits_each_loan() {
+        let exposure = Exposure::new(&[holding(1, "A", "LV", 5)], euros(95));
+        let limits = limits(None, None, Some(10.0));
+        assert_eq!(exposure.headroom(&limits, 1, "A", "LV"), euros(5));
+        assert_eq!(exposure.headroom(&limits, 2, "A", "LV"), euros(10));
+    }
+
+    #[test]
+    fn takes_the_tightest_limit_and_never_goes_below_zero() {
+        let exposure = Exposure::new(
+            &[holding(1, "A", "LV", 60), holding(2, "B", "EE", 10)],
+            euros(30),
+        );
+        let limits = limits(Some(50.0), Some(40.0), Some(20.0));
+        assert_eq!(exposure.headroom(&limits, 3, "A", "EE"), Money::ZERO);
+        assert_eq!(exposure.headroom(&limits, 3, "B", "EE"), euros(20));
+        assert_eq!(exposure.headroom(&limits, 2, "B", "EE"), euros(10));
+    }
+
+    #[test]
+    fn investing_the_cash_keeps_the_portfolio_size() {
+        let mut exposure = Exposure::new(&[], euros(100));
+        let limits = limits(Some(50.0), None, None);
+        exposure.add(&holding(1, "A", "LV", 20));
+        assert_eq!(exposure.headroom(&limits, 2, "A", "LV"), euros(30));
+        assert_eq!(exposure.headroom(&limits, 2, "B", "LV"), euros(50));
+    }
+}
@@ -0,0 +1,140 @@
+use std::time::Duration;
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// Why a call to a platform failed. Tells the caller whether to retry, skip
+/// the loan or give up.
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("Authentication failed: {0}")]
+    Auth(String),
+    #[error("Two-factor authentication rejected: {0}")]
+    TwoFactor(String),
+    #[error("Rate limited by the platform")]
+    RateLimited { retry_after: Option<Duration> },
+    #[error("Insufficient funds: {0}")]
+    InsufficientFunds(String),
+    #[error("Loan no longer available: {0}")]
+    LoanUnavailable(String),
+    /// The platform answered with something we don't understand, most likely
+    /// its API changed.
+    #[error("Failed to deserialize response: {source}. Raw response: {body}")]
+    Deserialize {
+        source: serde_json::Error,
+        body: String,
+    },
+    #[error("Request failed. Status code: {status}, response: {body}")]
+    Api {
+        status: reqwest::StatusCode,
+        body: String,
+    },
+    #[error("Failed to reach the platform")]
+    Transport(#[from] reqwest::Error),
+    #[error("Request middleware failed: {0:#}")]
+    Middleware(anyhow::Error),
+}
+
+impl Error {
+    /// The message followed by its sources, like `{:#}` of anyhow, for the
+    /// logs and the ledger where the message alone wouldn't say why.
+    pub fn report(&self) -> String {
+        let mut report = self.to_string();
+        let mut source = std::error::Error::source(self);
+        while let Some(e) = source {
+            report.push_str(&format!(": {}", e));
+            source = e.source();
+        }
+        report
+    }
+}
+
+impl From<reqwest_middleware::Error> for Error {
+    fn from(e: reqwest_middleware::Error) -> Self {
+        match e {
+            reqwest_middleware::Error::Reqwest(e) => Error::Transport(e),
+            reqwest_middleware::Error::Middleware(e) => Error::Middleware(e),
+        }
+    }
+}
+
+impl Error {
+    /// Process exit code for a run that ended in this error, so systemd can
+    /// tell failures apart.
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            Error::Auth(_) => 2,
+            Error::TwoFactor(_) => 3,
+            Error::RateLimited { .. } => 4,
+            Error::InsufficientFunds(_) => 5,
+            Error::LoanUnavailable(_) => 6,
+            Error::Deserialize { .. } => 7,
+            Error::Api { .. } => 8,
+            Error::Transport(_) | Error::Middleware(_) => 9,
+        }
+    }
+}
+
+/// Exit code for any error a run ended in, 1 for errors that didn't come
+/// from a platform.
+pub fn exit_code(error: &anyhow::Error) -> u8 {
+    error
+        .downcast_ref::<Error>()
+        .map(Error::exit_code)
+        .unwrap_or(1)
+}
+
+/// Turns an unsuccessful response into an error. 401 and 403 become
+/// [`Error::Auth`], 429 becomes [`Error::RateLimited`].
+pub async fn check(response: reqwest::Response) -> Result<reqwest::Response> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+
+    let retry_after = response
+        .headers()
+        .get(reqwest::header::RETRY_AFTER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse().ok())
+        .map(Duration::from_secs);
+    let body = response
+        .text()
+        .await
+        .unwrap_or_else(|_| "Failed to read response body.".to_string());
+
+    Err(match status {
+        reqwest::StatusCode::UNAUTHORIZED | reqwest::StatusCode::FORBIDDEN => Error::Auth(body),
+        reqwest::StatusCode::TOO_MANY_REQUESTS => Error::RateLimited { retry_after },
+        _ => Error::Api { status, body },
+    })
+}
+
+/// Checks the response and deserializes its body.
+pub async fn json<T: serde::de::DeserializeOwned>(response: reqwest::Response) -> Result<T> {
+    let body = check(response).await?.text().await?;
+    serde_json::from_str(&body).map_err(|source| Error::Deserialize { source, body })
+}
+
+/// Platforms answer a refused investment with a 4xx and a message saying
+/// why, this picks the matching error.
+pub fn classify_investment_error(e: Error) -> Error {
+    match e {
+        Error::Api { status, body } if status == reqwest::StatusCode::NOT_FOUND => {
+            Error::LoanUnavailable(body)
+        }
+        Error::Api { status, body } if status.is_client_error() => {
+            let message = body.to_lowercase();
+            if message.contains("insufficient") || message.contains("not enough") {
+                Error::InsufficientFunds(body)
+            } else if message.contains("not available")
+                || message.contains("fully funded")
+                || message.contains("exceeds")
+            {
+                Error::LoanUnavailable(body)
+            } else {
+                Error::Api { status, body }
+            }
+        }
+        e => e,
+    }
+}
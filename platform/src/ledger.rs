@@ -0,0 +1,186 @@
+//! A record of every run, the loans considered and what was done with them.
+
+use anyhow::{Context, Result};
+use rusqlite::{params, Connection, OptionalExtension};
+use std::path::Path;
+use xdg::BaseDirectories;
+
+use crate::{Loan, Money};
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS runs (
+    id INTEGER PRIMARY KEY,
+    platform TEXT NOT NULL,
+    dry_run INTEGER NOT NULL,
+    started_at TEXT NOT NULL DEFAULT (datetime('now')),
+    finished_at TEXT
+);
+CREATE TABLE IF NOT EXISTS balances (
+    run_id INTEGER NOT NULL REFERENCES runs(id),
+    currency TEXT NOT NULL,
+    -- In cents, like every amount
+    balance INTEGER NOT NULL,
+    PRIMARY KEY (run_id, currency)
+);
+CREATE TABLE IF NOT EXISTS decisions (
+    id INTEGER PRIMARY KEY,
+    run_id INTEGER NOT NULL REFERENCES runs(id),
+    loan_id INTEGER NOT NULL,
+    interest_rate REAL NOT NULL,
+    term INTEGER NOT NULL,
+    originator TEXT NOT NULL,
+    country TEXT NOT NULL,
+    currency TEXT NOT NULL,
+    outcome TEXT NOT NULL,
+    reason TEXT,
+    amount INTEGER,
+    response TEXT,
+    decided_at TEXT NOT NULL DEFAULT (datetime('now'))
+);
+CREATE INDEX IF NOT EXISTS decisions_loan_id ON decisions(loan_id);
+";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Outcome {
+    Skipped,
+    Invested,
+    Failed,
+    /// Put up for sale on a secondary market.
+    Listed,
+    /// Taken off a secondary market.
+    Cancelled,
+}
+
+impl Outcome {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Outcome::Skipped => "skipped",
+            Outcome::Invested => "invested",
+            Outcome::Failed => "failed",
+            Outcome::Listed => "listed",
+            Outcome::Cancelled => "cancelled",
+        }
+    }
+}
+
+/// What happened to a single loan in a run.
+pub struct Decision<'a> {
+    pub loan: &'a Loan,
+    pub outcome: Outcome,
+    pub reason: Option<&'a str>,
+    pub amount: Option<Money>,
+    pub response: Option<&'a str>,
+}
+
+pub struct Ledger {
+    conn: Connection,
+    run_id: Option<i64>,
+}
+
+impl Ledger {
+    pub fn open(path: &Path) -> Result<Ledger> {
+        let conn = Connection::open(path)
+            .with_context(|| format!("Failed to open ledger at {:?}", path))?;
+        conn.execute_batch(SCHEMA)
+            .context("Failed to create ledger schema")?;
+        Ok(Ledger { conn, run_id: None })
+    }
+
+    /// Opens `ledger.sqlite` in the XDG data directory of `app_name`.
+    pub fn open_default(app_name: &str) -> Result<Ledger> {
+        let path = BaseDirectories::with_prefix(app_name)?
+            .place_data_file("ledger.sqlite")
+            .context("Failed to create data directory")?;
+        Ledger::open(&path)
+    }
+
+    pub fn start_run(&mut self, platform: &str, dry_run: bool) -> Result<()> {
+        self.conn.execute(
+            "INSERT INTO runs (platform, dry_run) VALUES (?1, ?2)",
+            params![platform, dry_run],
+        )?;
+        self.run_id = Some(self.conn.last_insert_rowid());
+        Ok(())
+    }
+
+    /// Records the cash available in `currency`.
+    pub fn record_balance(&self, currency: &str, balance: Money) -> Result<()> {
+        self.conn.execute(
+            "INSERT OR REPLACE INTO balances (run_id, currency, balance) VALUES (?1, ?2, ?3)",
+            params![self.current_run()?, currency, balance],
+        )?;
+        Ok(())
+    }
+
+    pub fn record(&self, decision: &Decision) -> Result<()> {
+        self.conn.execute(
+            "INSERT INTO decisions
+                (run_id, loan_id, interest_rate, term, originator, country,
+                 currency, outcome, reason, amount, response)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+            params![
+                self.current_run()?,
+                decision.loan.id,
+                decision.loan.interest_rate,
+                decision.loan.term,
+                decision.loan.originator,
+                decision.loan.country,
+                decision.loan.currency,
+                decision.outcome.as_str(),
+                decision.reason,
+                decision.amount,
+                decision.response,
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn finish_run(&mut self) -> Result<()> {
+        self.conn.execute(
+            "UPDATE runs SET finished_at = datetime('now') WHERE id = ?1",
+            params![self.current_run()?],
+        )?;
+        self.run_id = None;
+        Ok(())
+    }
+
+    /// Whether a run that wasn't a dry run invested in `loan_id` on
+    /// `platform` before.
+    pub fn holds(&self, platform: &str, loan_id: i64) -> Result<bool> {
+        let held = self.conn.query_row(
+            "SELECT EXISTS (
+                SELECT 1 FROM decisions
+                JOIN runs ON runs.id = decisions.run_id
+                WHERE runs.platform = ?1 AND runs.dry_run = 0
+                  AND decisions.loan_id = ?2 AND decisions.outcome = 'invested'
+            )",
+            params![platform, loan_id],
+            |row| row.get(0),
+        )?;
+        Ok(held)
+    }
+
+    /// Whether a run that wasn't a dry run put `loan_id` on `platform` up
+    /// for sale and didn't take it off again.
+    pub fn listed(&self, platform: &str, loan_id: i64) -> Result<bool> {
+        let outcome: Option<String> = self
+            .conn
+            .query_row(
+                "SELECT decisions.outcome FROM decisions
+                JOIN runs ON runs.id = decisions.run_id
+                WHERE runs.platform = ?1 AND runs.dry_run = 0
+                  AND decisions.loan_id = ?2
+                  AND decisions.outcome IN ('listed', 'cancelled')
+                ORDER BY decisions.id DESC LIMIT 1",
+                params![platform, loan_id],
+                |row| row.get(0),
+            )
+            .optional()?;
+        Ok(outcome.as_deref() == Some(Outcome::Listed.as_str()))
+    }
+
+    fn current_run(&self) -> Result<i64> {
+        self.run_id
+            .ok_or_else(|| anyhow::anyhow!("No run started in the ledger"))
+    }
+}
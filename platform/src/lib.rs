@@ -0,0 +1,253 @@
+use anyhow::Result;
+use log::info;
+
+pub mod allocation;
+pub mod config;
+pub mod daemon;
+pub mod diversification;
+pub mod error;
+pub mod ledger;
+pub mod money;
+pub mod otp;
+
+pub use allocation::Allocation;
+pub use config::{default_currencies, deserialize_url, endpoint, read_config};
+pub use diversification::{Exposure, Holding, Limits};
+pub use error::{exit_code, Error};
+pub use ledger::{Decision, Ledger, Outcome};
+pub use money::Money;
+pub use otp::{fetch_totp, OtpResponse, TfaAuth, TfaTls};
+
+/// How long to back off when rate limited and the platform doesn't say.
+const RATE_LIMIT_DELAY: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// A loan on a platform's primary market, reduced to what the bots base
+/// their decisions on.
+#[derive(Debug, Clone)]
+pub struct Loan {
+    pub id: i64,
+    pub interest_rate: f32,
+    pub term: i32,
+    pub available_to_invest: Money,
+    pub allowed_to_invest: bool,
+    pub originator: String,
+    pub country: String,
+    /// ISO code of the currency the loan is in.
+    pub currency: String,
+}
+
+/// A P2P lending platform the bots can invest on.
+///
+/// Implementations keep whatever session state the platform needs between
+/// calls; `login` is always called first.
+#[async_trait::async_trait]
+pub trait Platform {
+    /// Name used in log lines.
+    fn name(&self) -> &str;
+
+    /// The smallest amount the platform accepts for an investment.
+    fn min_investment(&self) -> Money;
+
+    async fn login(&mut self) -> Result<(), Error>;
+
+    /// Cash in `currency` available for investing.
+    async fn balance(&mut self, currency: &str) -> Result<Money, Error>;
+
+    /// The loans in `currency`.
+    async fn loans(&mut self, currency: &str) -> Result<Vec<Loan>, Error>;
+
+    /// What is currently invested in `currency`. Platforms that can't tell
+    /// report an empty portfolio.
+    async fn holdings(&mut self, _currency: &str) -> Result<Vec<Holding>, Error> {
+        Ok(Vec::new())
+    }
+
+    /// Invests `amount` in `loan`, returning the platform's response body.
+    async fn invest(&mut self, loan: &Loan, amount: Money) -> Result<String, Error>;
+}
+
+/// Investment criteria shared by all bots.
+pub struct Criteria {
+    pub max_term: i32,
+    pub min_interest_rate: f32,
+    pub limits: Limits,
+    pub allocation: Allocation,
+    /// ISO codes of the currencies to invest in, each balance only in loans
+    /// of its own currency.
+    pub currencies: Vec<String>,
+}
+
+pub fn filter_desirable_loans(loans: Vec<Loan>, criteria: &Criteria) -> Vec<Loan> {
+    let mut desirable_loans: Vec<Loan> = loans
+        .into_iter()
+        .filter(|loan| {
+            loan.allowed_to_invest
+                && loan.term <= criteria.max_term
+                && loan.interest_rate >= criteria.min_interest_rate
+        })
+        .collect();
+
+    desirable_loans.sort_by(|a, b| {
+        b.interest_rate
+            .partial_cmp(&a.interest_rate)
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+
+    desirable_loans
+}
+
+/// Logs in and, for each currency, invests the available balance in the
+/// desirable loans, highest interest first. Every desirable loan ends up in
+/// the ledger, together with what was done with it.
+pub async fn run<P: Platform + Send>(
+    platform: &mut P,
+    criteria: &Criteria,
+    ledger: &mut Ledger,
+) -> Result<()> {
+    ledger.start_run(platform.name(), std::env::var("DRY_RUN").is_ok())?;
+    platform.login().await?;
+    for currency in &criteria.currencies {
+        invest_balance(platform, criteria, ledger, currency).await?;
+    }
+    ledger.finish_run()
+}
+
+async fn invest_balance<P: Platform + Send>(
+    platform: &mut P,
+    criteria: &Criteria,
+    ledger: &mut Ledger,
+    currency: &str,
+) -> Result<()> {
+    // 1. Fetch balance
+    let balance = platform.balance(currency).await?;
+    info!(
+        "{}: available balance: {} {}",
+        platform.name(),
+        balance,
+        currency
+    );
+    ledger.record_balance(currency, balance)?;
+
+    let investable = criteria.allocation.investable(balance);
+    let minimum = criteria.allocation.minimum(platform.min_investment());
+    if investable < minimum {
+        info!("Insufficient balance to invest.");
+        return Ok(());
+    }
+
+    // 2. Fetch loans
+    let loans: Vec<Loan> = platform
+        .loans(currency)
+        .await?
+        .into_iter()
+        .filter(|loan| loan.currency == currency)
+        .collect();
+    info!("Available loans: {}", loans.len());
+
+    // 3. Select loans to invest
+    let desirable_loans = filter_desirable_loans(loans, criteria);
+    info!("Desirable loans: {}", desirable_loans.len());
+
+    // 4. Invest in the selected loans, without concentrating too much of
+    // the portfolio in one originator, country or loan. The holdings are
+    // only fetched when there's a limit to keep to.
+    let holdings = if criteria.limits.any() {
+        platform.holdings(currency).await?
+    } else {
+        Vec::new()
+    };
+    let mut exposure = Exposure::new(&holdings, investable);
+    let mut available_money = investable;
+    for loan in &desirable_loans {
+        let skip = |reason: &str| {
+            ledger.record(&Decision {
+                loan,
+                outcome: Outcome::Skipped,
+                reason: Some(reason),
+                amount: None,
+                response: None,
+            })
+        };
+
+        if available_money < minimum {
+            skip("no funds left")?;
+            continue;
+        }
+        if ledger.holds(platform.name(), loan.id)? {
+            info!("Skipping loan {}, already invested in it.", loan.id);
+            skip("already invested")?;
+            continue;
+        }
+        let headroom =
+            exposure.headroom(&criteria.limits, loan.id, &loan.originator, &loan.country);
+        let upper_bound = available_money.min(loan.available_to_invest).min(headroom);
+        let investment_amount = match criteria
+            .allocation
+            .amount(upper_bound, platform.min_investment())
+        {
+            Some(amount) => amount,
+            None => {
+                info!(
+                    "Skipping loan {}, can invest at most {}.",
+                    loan.id, upper_bound
+                );
+                skip(&format!("can invest at most {}", upper_bound))?;
+                continue;
+            }
+        };
+
+        let response = match invest(platform, loan, investment_amount).await {
+            Ok(body) => body,
+            Err(e) => {
+                eprintln!("Failed to invest in loan {}: {}", loan.id, e.report());
+                ledger.record(&Decision {
+                    loan,
+                    outcome: Outcome::Failed,
+                    reason: None,
+                    amount: Some(investment_amount),
+                    response: Some(&e.report()),
+                })?;
+                match e {
+                    // Someone else was quicker, try the next one
+                    Error::LoanUnavailable(_) => continue,
+                    Error::InsufficientFunds(_) => break,
+                    _ => return Err(e.into()),
+                }
+            }
+        };
+        ledger.record(&Decision {
+            loan,
+            outcome: Outcome::Invested,
+            reason: None,
+            amount: Some(investment_amount),
+            response: Some(&response),
+        })?;
+
+        exposure.add(&Holding {
+            loan_id: loan.id,
+            originator: loan.originator.clone(),
+            country: loan.country.clone(),
+            amount: investment_amount,
+        });
+        available_money -= investment_amount;
+    }
+
+    Ok(())
+}
+
+/// Invests, trying once more when the platform asks to slow down.
+async fn invest<P: Platform + Send>(
+    platform: &mut P,
+    loan: &Loan,
+    amount: Money,
+) -> Result<String, Error> {
+    match platform.invest(loan, amount).await {
+        Err(Error::RateLimited { retry_after }) => {
+            let delay = retry_after.unwrap_or(RATE_LIMIT_DELAY);
+            info!("Rate limited, retrying in {} seconds", delay.as_secs());
+            tokio::time::sleep(delay).await;
+            platform.invest(loan, amount).await
+        }
+        result => result,
+    }
+}
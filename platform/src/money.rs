@@ -0,0 +1,278 @@
+//! Amounts of money, kept in the currency's minor units (cents) so adding,
+//! subtracting and rounding are exact.
+
+use std::fmt;
+use std::iter::Sum;
+use std::ops::{Add, AddAssign, Sub, SubAssign};
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct Money(i64);
+
+impl Money {
+    pub const ZERO: Money = Money(0);
+    /// Stands in for "no limit".
+    pub const MAX: Money = Money(i64::MAX);
+
+    pub const fn from_cents(cents: i64) -> Money {
+        Money(cents)
+    }
+
+    pub fn cents(self) -> i64 {
+        self.0
+    }
+
+    /// Rounds to the nearest cent. Only for amounts that arrive as floats,
+    /// like JSON numbers and the config.
+    pub fn from_f64(amount: f64) -> Money {
+        Money((amount * 100.0).round() as i64)
+    }
+
+    pub fn to_f64(self) -> f64 {
+        self.0 as f64 / 100.0
+    }
+
+    /// `percent` of this amount, rounded down to the cent.
+    pub fn percent(self, percent: f64) -> Money {
+        Money((self.0 as f64 * percent / 100.0).floor() as i64)
+    }
+}
+
+impl Add for Money {
+    type Output = Money;
+
+    fn add(self, other: Money) -> Money {
+        Money(self.0.saturating_add(other.0))
+    }
+}
+
+impl AddAssign for Money {
+    fn add_assign(&mut self, other: Money) {
+        *self = *self + other;
+    }
+}
+
+impl Sub for Money {
+    type Output = Money;
+
+    fn sub(self, other: Money) -> Money {
+        Money(self.0.saturating_sub(other.0))
+    }
+}
+
+impl SubAssign for Money {
+    fn sub_assign(&mut self, other: Money) {
+        *self = *self - other;
+    }
+}
+
+impl Sum for Money {
+    fn sum<I: Iterator<Item = Money>>(iter: I) -> Money {
+        iter.fold(Money::ZERO, Add::add)
+    }
+}
+
+/// Always two decimals, like `12.30`. This is also what goes into request
+/// payloads.
+impl fmt::Display for Money {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let sign = if self.0 < 0 { "-" } else { "" };
+        let cents = self.0.unsigned_abs();
+        write!(f, "{}{}.{:02}", sign, cents / 100, cents % 100)
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("Invalid amount: {0:?}")]
+pub struct ParseMoneyError(String);
+
+/// Parses decimal amounts like `12`, `12.3` or `-12.34` without going
+/// through a float. Digits past the cents are rounded half up.
+impl FromStr for Money {
+    type Err = ParseMoneyError;
+
+    fn from_str(s: &str) -> Result<Money, ParseMoneyError> {
+        let error = || ParseMoneyError(s.to_string());
+        let trimmed = s.trim();
+        let (negative, digits) = match trimmed.strip_prefix('-') {
+            Some(digits) => (true, digits),
+            None => (false, trimmed),
+        };
+        let (units, fraction) = digits.split_once('.').unwrap_or((digits, ""));
+        let all_digits = |part: &str| part.bytes().all(|b| b.is_ascii_digit());
+        if (units.is_empty() && fraction.is_empty()) || !all_digits(units) || !all_digits(fraction)
+        {
+            return Err(error());
+        }
+
+        let units: i64 = if units.is_empty() {
+            0
+        } else {
+            units.parse().map_err(|_| error())?
+        };
+        let mut fraction_digits = fraction.bytes().map(|b| (b - b'0') as i64);
+        let tens = fraction_digits.next().unwrap_or(0);
+        let ones = fraction_digits.next().unwrap_or(0);
+        let round_up = fraction_digits.next().unwrap_or(0) >= 5;
+
+        let cents = units
+            .checked_mul(100)
+            .and_then(|cents| cents.checked_add(tens * 10 + ones + round_up as i64))
+            .ok_or_else(error)?;
+        Ok(Money(if negative { -cents } else { cents }))
+    }
+}
+
+impl serde::Serialize for Money {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// Platforms send amounts as strings as well as numbers, both are accepted.
+impl<'de> serde::Deserialize<'de> for Money {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Money, D::Error> {
+        struct Visitor;
+
+        impl serde::de::Visitor<'_> for Visitor {
+            type Value = Money;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("an amount of money")
+            }
+
+            fn visit_str<E: serde::de::Error>(self, v: &str) -> Result<Money, E> {
+                v.parse().map_err(E::custom)
+            }
+
+            fn visit_i64<E: serde::de::Error>(self, v: i64) -> Result<Money, E> {
+                v.checked_mul(100)
+                    .map(Money)
+                    .ok_or_else(|| E::custom("amount too large"))
+            }
+
+            fn visit_u64<E: serde::de::Error>(self, v: u64) -> Result<Money, E> {
+                i64::try_from(v)
+                    .map_err(|_| E::custom("amount too large"))
+                    .and_then(|v| self.visit_i64(v))
+            }
+
+            fn visit_f64<E: serde::de::Error>(self, v: f64) -> Result<Money, E> {
+                Ok(Money::from_f64(v))
+            }
+        }
+
+        deserializer.deserialize_any(Visitor)
+    }
+}
+
+/// Stored as a whole number of cents, so sums in the ledger stay exact.
+impl rusqlite::ToSql for Money {
+    fn to_sql(&self) -> rusqlite::Result<rusqlite::types::ToSqlOutput<'_>> {
+        Ok(rusqlite::types::ToSqlOutput::from(self.0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(s: &str) -> Result<i64, ParseMoneyError> {
+        s.parse::<Money>().map(Money::cents)
+    }
+
+    #[test]
+    fn parses_decimal_amounts() {
+        assert_eq!(parse("12").unwrap(), 1200);
+        assert_eq!(parse("12.3").unwrap(), 1230);
+        assert_eq!(parse("12.34").unwrap(), 1234);
+        assert_eq!(parse(" 12.34\n").unwrap(), 1234);
+        assert_eq!(parse(".5").unwrap(), 50);
+        assert_eq!(parse("5.").unwrap(), 500);
+        assert_eq!(parse("0").unwrap(), 0);
+    }
+
+    #[test]
+    fn rounds_the_third_decimal_half_up() {
+        assert_eq!(parse("12.340000152").unwrap(), 1234);
+        assert_eq!(parse("12.344").unwrap(), 1234);
+        assert_eq!(parse("12.345").unwrap(), 1235);
+        assert_eq!(parse("0.995").unwrap(), 100);
+    }
+
+    #[test]
+    fn parses_negative_amounts() {
+        assert_eq!(parse("-12.34").unwrap(), -1234);
+        assert_eq!(parse("-0.05").unwrap(), -5);
+        // Half away from zero.
+        assert_eq!(parse("-1.005").unwrap(), -101);
+    }
+
+    #[test]
+    fn rejects_junk() {
+        for junk in [
+            "", " ", ".", "-", "-.", "abc", "1.2.3", "1,50", "1e5", "+5", "--5", "5-",
+        ] {
+            assert!(parse(junk).is_err(), "{:?}", junk);
+        }
+    }
+
+    #[test]
+    fn rejects_overflow() {
+        assert_eq!(parse("92233720368547758.07").unwrap(), i64::MAX);
+        assert!(parse("92233720368547758.08").is_err());
+        assert!(parse("99999999999999999999").is_err());
+    }
+
+    #[test]
+    fn displays_two_decimals() {
+        assert_eq!(Money::from_cents(1234).to_string(), "12.34");
+        assert_eq!(Money::from_cents(1230).to_string(), "12.30");
+        assert_eq!(Money::from_cents(5).to_string(), "0.05");
+        assert_eq!(Money::from_cents(0).to_string(), "0.00");
+        assert_eq!(Money::from_cents(-5).to_string(), "-0.05");
+        assert_eq!(Money::from_cents(-1234).to_string(), "-12.34");
+    }
+
+    #[test]
+    fn display_round_trips() {
+        for cents in [0, 1, 99, 100, 1234, -1, -1234, i64::MAX, i64::MIN + 1] {
+            let money = Money::from_cents(cents);
+            assert_eq!(money.to_string().parse::<Money>().unwrap(), money);
+        }
+    }
+
+    #[test]
+    fn deserializes_strings_ints_and_floats() {
+        let money = |json: &str| serde_json::from_str::<Money>(json).map(Money::cents);
+        assert_eq!(money(r#""12.340000152""#).unwrap(), 1234);
+        assert_eq!(money("12").unwrap(), 1200);
+        assert_eq!(money("-3").unwrap(), -300);
+        assert_eq!(money("12.34").unwrap(), 1234);
+        assert_eq!(money("12.340000152").unwrap(), 1234);
+        assert_eq!(money("0.30000000000000004").unwrap(), 30);
+        assert_eq!(money("0.015").unwrap(), 2);
+        assert!(money("92233720368547759").is_err());
+        assert!(money("18446744073709551615").is_err());
+        assert!(money(r#""junk""#).is_err());
+        assert!(money("null").is_err());
+    }
+
+    #[test]
+    fn serializes_as_a_string() {
+        let json = serde_json::to_string(&Money::from_cents(1230)).unwrap();
+        assert_eq!(json, r#""12.30""#);
+    }
+
+    #[test]
+    fn stores_whole_cents() {
+        let conn = rusqlite::Connection::open_in_memory().unwrap();
+        let (kind, cents): (String, i64) = conn
+            .query_row("SELECT typeof(?1), ?1", [Money::from_cents(1234)], |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })
+            .unwrap();
+        assert_eq!(kind, "integer");
+        assert_eq!(cents, 1234);
+    }
+}
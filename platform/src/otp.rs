@@ -0,0 +1,171 @@
+use crate::error::{self, Error, Result};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[derive(serde::Deserialize, serde::Serialize, Debug)]
+pub struct OtpResponse {
+    pub totp: String,
+}
+
+/// Who the bot is to the `tfa` server, from the `[tfa_auth]` table of the
+/// config. The key never goes over the wire, requests are signed with it.
+#[derive(serde::Deserialize, Debug, Clone)]
+pub struct TfaAuth {
+    pub client: String,
+    pub key: String,
+}
+
+impl TfaAuth {
+    /// The `Authorization` header for a request of `path` at `timestamp`
+    /// (seconds since the epoch): `HMAC <client>:<timestamp>:<signature>`,
+    /// the signature being the hex HMAC-SHA256 of `<timestamp> <path>`.
+    pub fn authorization(&self, path: &str, timestamp: u64) -> String {
+        let mut mac = Hmac::<Sha256>::new_from_slice(self.key.as_bytes())
+            .expect("HMAC takes keys of any length");
+        mac.update(format!("{} {}", timestamp, path).as_bytes());
+        let signature = hex::encode(mac.finalize().into_bytes());
+        format!("HMAC {}:{}:{}", self.client, timestamp, signature)
+    }
+}
+
+/// The client certificate to present to a `tfa` server behind mutual TLS,
+/// from the `[tfa_tls]` table of the config. The files are read along with
+/// the config, so a missing one stops the bot before it logs in.
+#[derive(serde::Deserialize, Debug, Clone)]
+#[serde(try_from = "TfaTlsFiles")]
+pub struct TfaTls {
+    identity: reqwest::Identity,
+    ca: Option<reqwest::Certificate>,
+}
+
+/// PEM files: the certificate, its private key and optionally the CA that
+/// signed the server's certificate when it isn't a public one.
+#[derive(serde::Deserialize)]
+struct TfaTlsFiles {
+    cert: PathBuf,
+    key: PathBuf,
+    ca: Option<PathBuf>,
+}
+
+impl TryFrom<TfaTlsFiles> for TfaTls {
+    type Error = String;
+
+    fn try_from(files: TfaTlsFiles) -> Result<TfaTls, String> {
+        let read = |path: &PathBuf| {
+            std::fs::read(path).map_err(|e| format!("Failed to read {:?}: {}", path, e))
+        };
+        let mut pem = read(&files.cert)?;
+        pem.push(b'\n');
+        pem.extend(read(&files.key)?);
+        let identity = reqwest::Identity::from_pem(&pem)
+            .map_err(|e| format!("Invalid client certificate or key: {}", e))?;
+        let ca = match &files.ca {
+            Some(path) => {
+                let pem = read(path)?;
+                // reqwest only looks inside when the client is built
+                let certs = rustls_pemfile::certs(&mut pem.as_slice()).unwrap_or_default();
+                if certs.is_empty() {
+                    return Err(format!("No CA certificate in {:?}", path));
+                }
+                Some(
+                    reqwest::Certificate::from_pem(&pem)
+                        .map_err(|e| format!("Invalid CA certificate in {:?}: {}", path, e))?,
+                )
+            }
+            None => None,
+        };
+        Ok(TfaTls { identity, ca })
+    }
+}
+
+/// Fetches a fresh TOTP code from the `tfa` server, presenting the client
+/// certificate and signing the request when configured. A refused request
+/// is an [`Error::TwoFactor`].
+pub async fn fetch_totp(
+    tfa_url: url::Url,
+    auth: Option<&TfaAuth>,
+    tls: Option<&TfaTls>,
+) -> Result<String> {
+    let mut client = reqwest::Client::builder();
+    if let Some(tls) = tls {
+        client = client.use_rustls_tls().identity(tls.identity.clone());
+        if let Some(ca) = &tls.ca {
+            client = client.add_root_certificate(ca.clone());
+        }
+    }
+    let mut request = client.build()?.get(tfa_url.clone());
+    if let Some(auth) = auth {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        request = request.header(
+            reqwest::header::AUTHORIZATION,
+            auth.authorization(tfa_url.path(), now),
+        );
+    }
+
+    let otp_response: OtpResponse = match error::json(request.send().await?).await {
+        Err(Error::Auth(body)) => {
+            return Err(Error::TwoFactor(format!(
+                "Refused by the tfa server: {}",
+                body
+            )))
+        }
+        result => result?,
+    };
+    Ok(otp_response.totp)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A CA and a `localhost` certificate signed by it with its key, made
+    /// with openssl.
+    fn testdata(name: &str) -> String {
+        format!("{}/testdata/{}", env!("CARGO_MANIFEST_DIR"), name)
+    }
+
+    fn tfa_tls(cert: &str, key: &str, ca: Option<&str>) -> std::result::Result<TfaTls, String> {
+        let mut table = toml::value::Table::new();
+        table.insert("cert".into(), testdata(cert).into());
+        table.insert("key".into(), testdata(key).into());
+        if let Some(ca) = ca {
+            table.insert("ca".into(), testdata(ca).into());
+        }
+        toml::Value::Table(table)
+            .try_into()
+            .map_err(|e: toml::de::Error| e.to_string())
+    }
+
+    #[test]
+    fn reads_the_client_certificate() {
+        let tls = tfa_tls("server.pem", "server.key", Some("ca.pem")).unwrap();
+        assert!(tls.ca.is_some());
+        assert!(tfa_tls("server.pem", "server.key", None)
+            .unwrap()
+            .ca
+            .is_none());
+    }
+
+    #[test]
+    fn refuses_missing_files() {
+        let error = tfa_tls("server.pem", "missing.key", None).unwrap_err();
+        assert!(error.contains("missing.key"), "{}", error);
+
+        let error = tfa_tls("server.pem", "server.key", Some("missing.pem")).unwrap_err();
+        assert!(error.contains("missing.pem"), "{}", error);
+    }
+
+    #[test]
+    fn refuses_invalid_files() {
+        let error = tfa_tls("server.pem", "ca.pem", None).unwrap_err();
+        assert!(error.contains("Invalid client certificate"), "{}", error);
+
+        let error = tfa_tls("server.pem", "server.key", Some("server.key")).unwrap_err();
+        assert!(error.contains("No CA certificate"), "{}", error);
+    }
+}
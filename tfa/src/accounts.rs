@@ -0,0 +1,236 @@
+//! The accounts codes are served for, from a TOML file with a table per
+//! account. Each is served at `/<name>`. The secret of an account is given
+//! by exactly one of `secret`, `secret_file`, `secret_env` or
+//! `secret_credential`, see [`crate::secrets`].
+
+use crate::secrets;
+use anyhow::Context;
+use std::collections::HashMap;
+use std::io::Write;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+use totp_rs::{Algorithm, Secret, TotpUrlError, TOTP};
+
+#[derive(serde::Deserialize, serde::Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Account {
+    /// Base32, like the platforms show it next to the QR code. The accounts
+    /// file must then only be readable by its owner, unless the secret is
+    /// encrypted.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub secret: Option<String>,
+    /// A file holding the secret, only readable by its owner. Relative to
+    /// the accounts file.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub secret_file: Option<PathBuf>,
+    /// An environment variable holding the secret.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub secret_env: Option<String>,
+    /// A systemd credential holding the secret, from
+    /// `$CREDENTIALS_DIRECTORY`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub secret_credential: Option<String>,
+    /// `SHA1`, `SHA256` or `SHA512`.
+    #[serde(default = "default_algorithm")]
+    pub algorithm: String,
+    #[serde(default = "default_digits")]
+    pub digits: usize,
+    /// Seconds a code is valid for.
+    #[serde(default = "default_period")]
+    pub period: u64,
+    /// Periods before and after the current one a code is still accepted
+    /// in.
+    #[serde(default = "default_skew")]
+    pub skew: u8,
+}
+
+fn default_algorithm() -> String {
+    "SHA1".to_string()
+}
+
+fn default_digits() -> usize {
+    6
+}
+
+fn default_period() -> u64 {
+    30
+}
+
+pub fn default_skew() -> u8 {
+    1
+}
+
+pub type Accounts = HashMap<String, TOTP>;
+
+pub fn load(path: &Path) -> anyhow::Result<Accounts> {
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read accounts from {:?}", path))?;
+    let accounts: HashMap<String, Account> =
+        toml::from_str(&contents).with_context(|| format!("Invalid accounts file {:?}", path))?;
+    anyhow::ensure!(!accounts.is_empty(), "No accounts in {:?}", path);
+
+    let inline_plaintext = accounts.values().any(|account| {
+        account
+            .secret
+            .as_deref()
+            .is_some_and(|secret| !secrets::is_encrypted(secret))
+    });
+    if inline_plaintext {
+        secrets::check_permissions(path)?;
+    }
+
+    let dir = path.parent().unwrap_or(Path::new(""));
+    let mut unlock = secrets::Unlock::default();
+    accounts
+        .into_iter()
+        .map(|(name, account)| {
+            let totp = totp(&name, account, dir, &mut unlock)
+                .with_context(|| format!("Invalid account {}", name))?;
+            Ok((name, totp))
+        })
+        .collect()
+}
+
+/// Appends the account to the accounts file, creating it only readable by
+/// its owner if it doesn't exist. Comments and the other accounts are left
+/// as they are.
+pub fn add(path: &Path, name: &str, account: &Account) -> anyhow::Result<()> {
+    check_name(name)?;
+    let exists = path.exists();
+    if exists {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read accounts from {:?}", path))?;
+        let existing: toml::value::Table = toml::from_str(&contents)
+            .with_context(|| format!("Invalid accounts file {:?}", path))?;
+        anyhow::ensure!(
+            !existing.contains_key(name),
+            "There's already an account {} in {:?}",
+            name,
+            path
+        );
+        let plaintext = account
+            .secret
+            .as_deref()
+            .is_some_and(|secret| !secrets::is_encrypted(secret));
+        if plaintext {
+            secrets::check_permissions(path)?;
+        }
+    }
+
+    let table = toml::to_string(&HashMap::from([(name, account)]))?;
+    let mut file = std::fs::OpenOptions::new()
+        .append(true)
+        .create(true)
+        .mode(0o600)
+        .open(path)
+        .with_context(|| format!("Failed to open {:?}", path))?;
+    let separator = if exists { "\n" } else { "" };
+    writeln!(file, "{}{}", separator, table.trim_end())
+        .with_context(|| format!("Failed to write to {:?}", path))?;
+    Ok(())
+}
+
+fn check_name(name: &str) -> anyhow::Result<()> {
+    anyhow::ensure!(
+        !name.is_empty()
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'),
+        "Names may only have letters, digits, '-' and '_'"
+    );
+    Ok(())
+}
+
+fn secret(account: &Account, dir: &Path) -> anyhow::Result<String> {
+    match (
+        &account.secret,
+        &account.secret_file,
+        &account.secret_env,
+        &account.secret_credential,
+    ) {
+        (Some(secret), None, None, None) => Ok(secret.clone()),
+        (None, Some(path), None, None) => secrets::read_file(&dir.join(path)),
+        (None, None, Some(name), None) => secrets::read_env(name),
+        (None, None, None, Some(name)) => secrets::read_credential(name),
+        _ => anyhow::bail!(
+            "Needs exactly one of secret, secret_file, secret_env and secret_credential"
+        ),
+    }
+}
+
+fn totp(
+    name: &str,
+    account: Account,
+    dir: &Path,
+    unlock: &mut secrets::Unlock,
+) -> anyhow::Result<TOTP> {
+    check_name(name)?;
+    let algorithm = match account.algorithm.to_uppercase().as_str() {
+        "SHA1" => Algorithm::SHA1,
+        "SHA256" => Algorithm::SHA256,
+        "SHA512" => Algorithm::SHA512,
+        other => anyhow::bail!("Unknown algorithm {}", other),
+    };
+    let secret = Secret::Encoded(unlock.secret(secret(&account, dir)?)?)
+        .to_bytes()
+        .map_err(|e| anyhow::anyhow!("Invalid secret: {:?}", e))?;
+
+    let new = |secret| {
+        TOTP::new(
+            algorithm,
+            account.digits,
+            account.skew,
+            account.period,
+            secret,
+            None,
+            String::new(),
+        )
+    };
+    match new(secret.clone()) {
+        Ok(totp) => Ok(totp),
+        // Esketit hands out shorter secrets than RFC 4226 asks for
+        Err(TotpUrlError::SecretSize(bits)) => {
+            println!("The secret of {} is only {} bits", name, bits);
+            Ok(TOTP::new_unchecked(
+                algorithm,
+                account.digits,
+                account.skew,
+                account.period,
+                secret,
+                None,
+                String::new(),
+            ))
+        }
+        Err(e) => Err(anyhow::anyhow!("{:?}", e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn account(toml: &str) -> Account {
+        toml::from_str(toml).unwrap()
+    }
+
+    #[test]
+    fn takes_the_secret_from_the_accounts_file() {
+        let account = account(r#"secret = "JBSWY3DPEHPK3PXP""#);
+        assert_eq!(
+            secret(&account, Path::new(".")).unwrap(),
+            "JBSWY3DPEHPK3PXP"
+        );
+    }
+
+    #[test]
+    fn needs_exactly_one_secret() {
+        for toml in [
+            "",
+            "secret = \"JBSWY3DPEHPK3PXP\"\nsecret_env = \"TFA_SECRET\"",
+            "secret_file = \"secret\"\nsecret_credential = \"secret\"",
+        ] {
+            let error = secret(&account(toml), Path::new(".")).unwrap_err();
+            assert!(error.to_string().contains("exactly one"), "{}", error);
+        }
+    }
+}
@@ -0,0 +1,244 @@
+//! Who may fetch codes. Each client has a key in the keys file and either
+//! sends it as a bearer token or signs the request with it, the way
+//! `platform::TfaAuth` does. A client only gets the codes of its own
+//! accounts.
+
+use anyhow::Context;
+use axum::extract::State;
+use axum::http::{header, Request, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::{secrets, ErrorResponse};
+
+/// Signatures are accepted this many seconds either side of our clock.
+const MAX_SKEW: u64 = 60;
+
+/// A client's entry in the keys file: just its key, for the account of
+/// the same name, or a table with the accounts it may fetch.
+#[derive(serde::Deserialize)]
+#[serde(untagged)]
+enum Client {
+    Key(String),
+    Accounts { key: String, accounts: Vec<String> },
+}
+
+impl Client {
+    fn key(&self) -> &str {
+        match self {
+            Client::Key(key) | Client::Accounts { key, .. } => key,
+        }
+    }
+}
+
+/// Key per client name, from a TOML file like `peerberry = "..."`.
+pub struct Keys(HashMap<String, Client>);
+
+impl Keys {
+    /// The keys can get codes out of the server, so like a secret the file
+    /// must only be readable by its owner.
+    pub fn load(path: &Path) -> anyhow::Result<Keys> {
+        secrets::check_permissions(path)?;
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read keys from {:?}", path))?;
+        let keys =
+            Keys::parse(&contents).with_context(|| format!("Invalid keys file {:?}", path))?;
+        anyhow::ensure!(!keys.0.is_empty(), "No keys in {:?}", path);
+        Ok(keys)
+    }
+
+    fn parse(contents: &str) -> anyhow::Result<Keys> {
+        Ok(Keys(toml::from_str(contents)?))
+    }
+
+    /// Whether `client` may fetch the code served at `path`.
+    fn may_fetch(&self, client: &str, path: &str) -> bool {
+        let account = path.strip_prefix('/').unwrap_or(path);
+        match self.0.get(client) {
+            Some(Client::Key(_)) => account == client,
+            Some(Client::Accounts { accounts, .. }) => accounts.iter().any(|a| a == account),
+            None => false,
+        }
+    }
+
+    /// The client behind an `Authorization` header for `path`, if it holds
+    /// a key. Either `Bearer <key>` or `HMAC <client>:<timestamp>:<signature>`
+    /// with the hex HMAC-SHA256 of `<timestamp> <path>` made with the key.
+    fn client(&self, authorization: &str, path: &str, now: u64) -> Option<&str> {
+        if let Some(token) = authorization.strip_prefix("Bearer ") {
+            return self
+                .0
+                .iter()
+                .find(|(_, entry)| same(entry.key().as_bytes(), token.as_bytes()))
+                .map(|(client, _)| client.as_str());
+        }
+
+        let mut parts = authorization.strip_prefix("HMAC ")?.splitn(3, ':');
+        let (client, timestamp, signature) = (parts.next()?, parts.next()?, parts.next()?);
+        let timestamp: u64 = timestamp.parse().ok()?;
+        if timestamp.abs_diff(now) > MAX_SKEW {
+            return None;
+        }
+        let (client, entry) = self.0.get_key_value(client)?;
+        let mut mac = Hmac::<Sha256>::new_from_slice(entry.key().as_bytes()).ok()?;
+        mac.update(format!("{} {}", timestamp, path).as_bytes());
+        mac.verify_slice(&hex::decode(signature).ok()?).ok()?;
+        Some(client)
+    }
+}
+
+/// Compares without giving away through timing how much matched.
+fn same(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+/// Lets only requests from a client with a key through, and only to its
+/// own accounts.
+pub async fn require_key<B>(
+    State(keys): State<Arc<Keys>>,
+    request: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let path = request.uri().path().to_string();
+    let client = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|authorization| keys.client(authorization, &path, now));
+
+    match client {
+        Some(client) if keys.may_fetch(client, &path) => {
+            println!("{} fetched {}", client, path);
+            next.run(request).await
+        }
+        Some(client) => {
+            eprintln!("Refused {} a request for {}", client, path);
+            let error = ErrorResponse {
+                error: format!("{} may not fetch {}", client, path),
+            };
+            (StatusCode::FORBIDDEN, Json(error)).into_response()
+        }
+        None => {
+            eprintln!("Refused a request for {}", path);
+            let error = ErrorResponse {
+                error: "Missing or invalid key".to_string(),
+            };
+            (StatusCode::UNAUTHORIZED, Json(error)).into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: u64 = 1_700_000_000;
+
+    fn keys() -> Keys {
+        Keys::parse(
+            r#"
+            peerberry = "peerberry-key"
+
+            [esketit]
+            key = "esketit-key"
+            accounts = ["esketit", "esketit-2"]
+            "#,
+        )
+        .unwrap()
+    }
+
+    fn hmac(client: &str, key: &str, timestamp: u64, path: &str) -> String {
+        let mut mac = Hmac::<Sha256>::new_from_slice(key.as_bytes()).unwrap();
+        mac.update(format!("{} {}", timestamp, path).as_bytes());
+        let signature = hex::encode(mac.finalize().into_bytes());
+        format!("HMAC {}:{}:{}", client, timestamp, signature)
+    }
+
+    #[test]
+    fn accepts_a_valid_signature() {
+        let authorization = hmac("peerberry", "peerberry-key", NOW - 30, "/peerberry");
+        assert_eq!(
+            keys().client(&authorization, "/peerberry", NOW),
+            Some("peerberry")
+        );
+    }
+
+    #[test]
+    fn refuses_a_tampered_signature() {
+        let authorization = hmac("peerberry", "peerberry-key", NOW, "/peerberry");
+        let last = if authorization.ends_with('0') {
+            "1"
+        } else {
+            "0"
+        };
+        let tampered = format!("{}{}", &authorization[..authorization.len() - 1], last);
+        assert_eq!(keys().client(&tampered, "/peerberry", NOW), None);
+        // Signed for another path.
+        assert_eq!(keys().client(&authorization, "/esketit", NOW), None);
+    }
+
+    #[test]
+    fn refuses_a_timestamp_outside_the_skew() {
+        let keys = keys();
+        for timestamp in [NOW - MAX_SKEW - 1, NOW + MAX_SKEW + 1] {
+            let authorization = hmac("peerberry", "peerberry-key", timestamp, "/peerberry");
+            assert_eq!(keys.client(&authorization, "/peerberry", NOW), None);
+        }
+    }
+
+    #[test]
+    fn refuses_an_unknown_client() {
+        let authorization = hmac("mintos", "peerberry-key", NOW, "/peerberry");
+        assert_eq!(keys().client(&authorization, "/peerberry", NOW), None);
+        assert_eq!(keys().client("Bearer mintos-key", "/peerberry", NOW), None);
+    }
+
+    #[test]
+    fn accepts_a_bearer_key() {
+        assert_eq!(
+            keys().client("Bearer esketit-key", "/esketit", NOW),
+            Some("esketit")
+        );
+        assert_eq!(keys().client("Bearer esketit-ke", "/esketit", NOW), None);
+    }
+
+    #[test]
+    fn only_serves_a_client_its_own_accounts() {
+        let keys = keys();
+        assert!(keys.may_fetch("peerberry", "/peerberry"));
+        assert!(!keys.may_fetch("peerberry", "/esketit"));
+        assert!(!keys.may_fetch("peerberry", "/peerberry-2"));
+        assert!(keys.may_fetch("esketit", "/esketit"));
+        assert!(keys.may_fetch("esketit", "/esketit-2"));
+        assert!(!keys.may_fetch("esketit", "/peerberry"));
+        assert!(!keys.may_fetch("mintos", "/mintos"));
+    }
+
+    #[test]
+    fn loads_keys_only_their_owner_can_read() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = std::env::temp_dir().join(format!("tfa-{}-keys.toml", std::process::id()));
+        std::fs::write(&path, "peerberry = \"peerberry-key\"\n").unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644)).unwrap();
+        let error = Keys::load(&path).err().unwrap();
+        assert!(error.to_string().contains("chmod 600"), "{}", error);
+
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600)).unwrap();
+        assert!(Keys::load(&path)
+            .unwrap()
+            .may_fetch("peerberry", "/peerberry"));
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,173 @@
+//! Adding an account from the `otpauth://` URI a platform shows as a QR
+//! code when setting up 2FA, keeping its algorithm, digits and period.
+
+use crate::{accounts, secrets};
+use anyhow::Context;
+use std::io::ErrorKind;
+use std::path::Path;
+use std::process::Command;
+use totp_rs::{TotpUrlError, TOTP};
+
+/// `source` is an `otpauth://` URI or an image of its QR code. Without it
+/// the URI is asked for, so it stays out of the shell history.
+pub fn run(
+    accounts_path: &Path,
+    source: Option<&str>,
+    name: Option<&str>,
+    encrypt: bool,
+) -> anyhow::Result<()> {
+    let uri = match source {
+        Some(uri) if is_uri(uri) => uri.to_string(),
+        Some(image) => decode_qr(Path::new(image))?,
+        None => rpassword::prompt_password("otpauth URI: ")?,
+    };
+    let totp = parse(uri.trim())?;
+    let name = match name {
+        Some(name) => name.to_string(),
+        None => default_name(&totp)?,
+    };
+
+    let mut secret = totp.get_secret_base32();
+    if encrypt {
+        secret = secrets::encrypt(&secret, &secrets::passphrase(true)?)?;
+    }
+    let account = accounts::Account {
+        secret: Some(secret),
+        secret_file: None,
+        secret_env: None,
+        secret_credential: None,
+        algorithm: totp.algorithm.to_string(),
+        digits: totp.digits,
+        period: totp.step,
+        skew: accounts::default_skew(),
+    };
+    accounts::add(accounts_path, &name, &account)?;
+
+    println!(
+        "Added {} ({}, {} digits, {}s) to {:?}, served at /{}",
+        name, account.algorithm, account.digits, account.period, accounts_path, name
+    );
+    Ok(())
+}
+
+fn is_uri(source: &str) -> bool {
+    source.starts_with("otpauth://") || source.starts_with("otpauth-migration://")
+}
+
+fn parse(uri: &str) -> anyhow::Result<TOTP> {
+    anyhow::ensure!(
+        !uri.starts_with("otpauth-migration:"),
+        "Google Authenticator exports (otpauth-migration://) aren't supported"
+    );
+    match TOTP::from_url(uri) {
+        Ok(totp) => Ok(totp),
+        // Short secrets are warned about, not refused, when loading
+        Err(TotpUrlError::SecretSize(_)) => {
+            TOTP::from_url_unchecked(uri).map_err(|e| anyhow::anyhow!("Invalid URI: {:?}", e))
+        }
+        Err(e) => Err(anyhow::anyhow!("Invalid URI: {:?}", e)),
+    }
+}
+
+/// The issuer, or else the account name, as a name fit for a path.
+fn default_name(totp: &TOTP) -> anyhow::Result<String> {
+    let label = totp
+        .issuer
+        .as_deref()
+        .filter(|issuer| !issuer.is_empty())
+        .unwrap_or(&totp.account_name);
+    let name: String = label
+        .to_lowercase()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .collect();
+    let name = name.trim_matches('-').to_string();
+    anyhow::ensure!(!name.is_empty(), "The URI has no name, pass --name");
+    Ok(name)
+}
+
+/// Reads the QR code with `zbarimg` from zbar, which reads PNG, JPEG and
+/// most other formats. zbar is optional, only this needs it.
+fn decode_qr(image: &Path) -> anyhow::Result<String> {
+    let output = match Command::new("zbarimg")
+        .args(["--quiet", "--raw", "-Sdisable", "-Sqrcode.enable"])
+        .arg(image)
+        .output()
+    {
+        Ok(output) => output,
+        Err(e) if e.kind() == ErrorKind::NotFound => anyhow::bail!(
+            "Reading QR codes needs zbarimg (apt install zbar-tools), \
+             or pass the otpauth:// URI instead of {:?}",
+            image
+        ),
+        Err(e) => return Err(e).context("Failed to run zbarimg"),
+    };
+    anyhow::ensure!(
+        output.status.success(),
+        "No QR code found in {:?}: {}",
+        image,
+        String::from_utf8_lossy(&output.stderr).trim()
+    );
+    let text = String::from_utf8(output.stdout).context("QR code isn't text")?;
+    text.lines()
+        .find(|line| is_uri(line))
+        .map(str::to_string)
+        .with_context(|| format!("No otpauth URI in the QR code in {:?}", image))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tells_uris_from_images() {
+        assert!(is_uri("otpauth://totp/Peerberry?secret=JBSWY3DPEHPK3PXP"));
+        assert!(is_uri("otpauth-migration://offline?data=abc"));
+        assert!(!is_uri("otpauth.png"));
+        assert!(!is_uri("otpauth-codes/peerberry.png"));
+    }
+
+    #[test]
+    fn keeps_the_algorithm_digits_and_period() {
+        let totp = parse(
+            "otpauth://totp/Esketit:me%40example.com?secret=JBSWY3DPEHPK3PXPJBSWY3DPEHPK3PXP\
+             &issuer=Esketit&algorithm=SHA256&digits=8&period=60",
+        )
+        .unwrap();
+        assert_eq!(totp.algorithm.to_string(), "SHA256");
+        assert_eq!(totp.digits, 8);
+        assert_eq!(totp.step, 60);
+        assert_eq!(totp.get_secret_base32(), "JBSWY3DPEHPK3PXPJBSWY3DPEHPK3PXP");
+    }
+
+    #[test]
+    fn accepts_short_secrets() {
+        let totp =
+            parse("otpauth://totp/Esketit:me?secret=JBSWY3DPEHPK3PXP&issuer=Esketit").unwrap();
+        assert_eq!(totp.get_secret_base32(), "JBSWY3DPEHPK3PXP");
+    }
+
+    #[test]
+    fn refuses_migration_exports() {
+        assert!(parse("otpauth-migration://offline?data=abc").is_err());
+    }
+
+    #[test]
+    fn names_the_account_after_the_issuer() {
+        let uri = "otpauth://totp/Esketit%20Invest:me%40example.com\
+                   ?secret=JBSWY3DPEHPK3PXP&issuer=Esketit%20Invest";
+        assert_eq!(
+            default_name(&parse(uri).unwrap()).unwrap(),
+            "esketit-invest"
+        );
+
+        let uri = "otpauth://totp/me%40example.com?secret=JBSWY3DPEHPK3PXP";
+        assert_eq!(
+            default_name(&parse(uri).unwrap()).unwrap(),
+            "me-example-com"
+        );
+
+        let uri = "otpauth://totp/%40?secret=JBSWY3DPEHPK3PXP";
+        assert!(default_name(&parse(uri).unwrap()).is_err());
+    }
+}
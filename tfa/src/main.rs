@@ -1,13 +1,60 @@
+use axum::extract::{Path, State};
 use axum::{http::StatusCode, routing::get, Json, Router};
 use clap::Parser;
 use serde::Serialize;
-use totp_rs::TOTP;
+use std::sync::Arc;
+
+mod accounts;
+mod auth;
+mod import;
+mod secrets;
+mod tls;
 
 #[derive(clap::Parser, Debug)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 struct Args {
-    host: std::net::SocketAddr,
-    esketit: String,
-    peerberry: String,
+    #[command(subcommand)]
+    command: Option<Command>,
+    #[arg(required = true)]
+    host: Option<std::net::SocketAddr>,
+    /// TOML file with a table per account, served at `/<name>`.
+    #[arg(required = true)]
+    accounts: Option<std::path::PathBuf>,
+    /// TOML file with a key per client, like `peerberry = "..."`. Without
+    /// it anyone who can reach the server gets codes.
+    #[arg(long)]
+    keys: Option<std::path::PathBuf>,
+    /// Serve over TLS with this certificate (chain), PEM.
+    #[arg(long, requires_all = ["tls_key", "client_ca"])]
+    tls_cert: Option<std::path::PathBuf>,
+    /// The private key of `--tls-cert`, PEM.
+    #[arg(long, requires = "tls_cert")]
+    tls_key: Option<std::path::PathBuf>,
+    /// Only serve clients with a certificate signed by one of these CAs, PEM.
+    #[arg(long, requires = "tls_cert")]
+    client_ca: Option<std::path::PathBuf>,
+}
+
+#[derive(clap::Subcommand, Debug)]
+enum Command {
+    /// Encrypt a secret with a passphrase, for the accounts file or a secret
+    /// file. Asks for both, the passphrase can also come from
+    /// `$TFA_PASSPHRASE` or the `tfa-passphrase` systemd credential.
+    Encrypt,
+    /// Add an account to the accounts file from an `otpauth://` URI or an
+    /// image of its QR code (read with `zbarimg`, when it is installed).
+    /// Asks for the URI when given neither.
+    Import {
+        accounts: std::path::PathBuf,
+        /// An `otpauth://` URI or an image file.
+        source: Option<String>,
+        /// Served at `/<name>`, the issuer in the URI by default.
+        #[arg(long)]
+        name: Option<String>,
+        /// Store the secret encrypted with a passphrase.
+        #[arg(long)]
+        encrypt: bool,
+    },
 }
 
 #[derive(Debug, Serialize)]
@@ -21,8 +68,17 @@ struct ErrorResponse {
 }
 
 async fn generate_totp(
-    totp: TOTP,
+    State(accounts): State<Arc<accounts::Accounts>>,
+    Path(name): Path<String>,
 ) -> Result<Json<TOTPResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let Some(totp) = accounts.get(&name) else {
+        return Err((
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse {
+                error: format!("No account {}", name),
+            }),
+        ));
+    };
     match totp.generate_current() {
         Ok(current_totp) => Ok(Json(TOTPResponse { totp: current_totp })),
         Err(_) => Err((
@@ -35,37 +91,65 @@ async fn generate_totp(
 }
 
 #[tokio::main]
-async fn main() {
+async fn main() -> anyhow::Result<()> {
     let args = Args::parse();
+    match &args.command {
+        Some(Command::Encrypt) => {
+            let secret = rpassword::prompt_password("Secret: ")?;
+            let passphrase = secrets::passphrase(true)?;
+            println!("{}", secrets::encrypt(secret.trim(), &passphrase)?);
+            return Ok(());
+        }
+        Some(Command::Import {
+            accounts,
+            source,
+            name,
+            encrypt,
+        }) => {
+            return import::run(accounts, source.as_deref(), name.as_deref(), *encrypt);
+        }
+        None => {}
+    }
+    let (Some(host), Some(accounts)) = (args.host, &args.accounts) else {
+        unreachable!("clap requires both without a subcommand");
+    };
 
-    let esketit = totp_rs::TOTP::new_unchecked(
-        totp_rs::Algorithm::SHA1,
-        6,
-        1,
-        30,
-        totp_rs::Secret::Encoded(args.esketit).to_bytes().unwrap(),
-        None,
-        "".to_string(),
-    );
+    let accounts = accounts::load(accounts)?;
+    let mut names: Vec<&String> = accounts.keys().collect();
+    names.sort();
+    println!("Serving codes for {:?}", names);
 
-    let peerberry = totp_rs::TOTP::new(
-        totp_rs::Algorithm::SHA1,
-        6,
-        1,
-        30,
-        totp_rs::Secret::Encoded(args.peerberry).to_bytes().unwrap(),
-        None,
-        "".to_string(),
-    )
-    .unwrap();
+    let mut app = Router::new()
+        .route("/:name", get(generate_totp))
+        .with_state(Arc::new(accounts));
+    match &args.keys {
+        Some(path) => {
+            let keys = Arc::new(auth::Keys::load(path)?);
+            app = app.layer(axum::middleware::from_fn_with_state(
+                keys,
+                auth::require_key,
+            ));
+        }
+        None => println!("No --keys given, serving codes to anyone"),
+    }
 
-    let app = Router::new()
-        .route("/peerberry", get(move || generate_totp(peerberry)))
-        .route("/esketit", get(move || generate_totp(esketit)));
+    if let (Some(cert), Some(key), Some(client_ca)) =
+        (&args.tls_cert, &args.tls_key, &args.client_ca)
+    {
+        let config = tls::config(cert, key, client_ca)?;
+        println!("Listening on {} (TLS, client certificates required)", host);
+        axum_server::bind_rustls(
+            host,
+            axum_server::tls_rustls::RustlsConfig::from_config(config),
+        )
+        .serve(app.into_make_service())
+        .await?;
+        return Ok(());
+    }
 
-    println!("Listening on {}", args.host);
-    axum::Server::bind(&args.host)
+    println!("Listening on {}", host);
+    axum::Server::bind(&host)
         .serve(app.into_make_service())
-        .await
-        .unwrap();
+        .await?;
+    Ok(())
 }
@@ -0,0 +1,216 @@
+//! Where the TOTP secrets come from, so they stay off the command line: the
+//! accounts file, a file of their own, an environment variable or a systemd
+//! credential. Each of these may hold the secret encrypted with a
+//! passphrase, as made by `tfa encrypt`.
+
+use anyhow::Context;
+use argon2::Argon2;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+
+/// Marks an encrypted secret: base64 of the salt, the nonce and the
+/// ciphertext.
+const ENCRYPTED: &str = "encrypted:";
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+
+/// The systemd credential and the environment variable the passphrase is
+/// read from, before asking for it.
+const PASSPHRASE_CREDENTIAL: &str = "tfa-passphrase";
+const PASSPHRASE_ENV: &str = "TFA_PASSPHRASE";
+
+/// Fails unless `path` is a regular file only its owner can read, as
+/// anything else could leak the secrets in it.
+pub fn check_permissions(path: &Path) -> anyhow::Result<()> {
+    let metadata = std::fs::metadata(path).with_context(|| format!("Failed to read {:?}", path))?;
+    anyhow::ensure!(metadata.is_file(), "{:?} isn't a file", path);
+    let mode = metadata.permissions().mode();
+    anyhow::ensure!(
+        mode & 0o077 == 0,
+        "{:?} can be read by others (mode {:o}), chmod 600 it",
+        path,
+        mode & 0o777
+    );
+    Ok(())
+}
+
+/// A file holding nothing but a secret, trailing newline aside.
+pub fn read_file(path: &Path) -> anyhow::Result<String> {
+    check_permissions(path)?;
+    let contents =
+        std::fs::read_to_string(path).with_context(|| format!("Failed to read {:?}", path))?;
+    Ok(contents.trim().to_string())
+}
+
+pub fn read_env(name: &str) -> anyhow::Result<String> {
+    std::env::var(name).with_context(|| format!("No environment variable {}", name))
+}
+
+/// A credential systemd passed in with `LoadCredential=` or
+/// `LoadCredentialEncrypted=`. systemd looks after its permissions.
+pub fn read_credential(name: &str) -> anyhow::Result<String> {
+    let path = credential_path(name)
+        .with_context(|| format!("No credential {}, $CREDENTIALS_DIRECTORY isn't set", name))?;
+    let contents =
+        std::fs::read_to_string(&path).with_context(|| format!("Failed to read {:?}", path))?;
+    Ok(contents.trim().to_string())
+}
+
+fn credential_path(name: &str) -> Option<PathBuf> {
+    std::env::var_os("CREDENTIALS_DIRECTORY").map(|dir| Path::new(&dir).join(name))
+}
+
+pub fn is_encrypted(value: &str) -> bool {
+    value.starts_with(ENCRYPTED)
+}
+
+/// Asks for the passphrase once, and only when a secret is encrypted.
+#[derive(Default)]
+pub struct Unlock {
+    passphrase: Option<String>,
+}
+
+impl Unlock {
+    /// The secret itself, decrypted if it is encrypted.
+    pub fn secret(&mut self, value: String) -> anyhow::Result<String> {
+        match value.strip_prefix(ENCRYPTED) {
+            Some(encrypted) => {
+                let passphrase = match &self.passphrase {
+                    Some(passphrase) => passphrase,
+                    None => self.passphrase.insert(passphrase(false)?),
+                };
+                decrypt(encrypted, passphrase)
+            }
+            None => Ok(value),
+        }
+    }
+}
+
+/// The passphrase from the systemd credential, the environment or else the
+/// terminal, typed twice when `confirm`.
+pub fn passphrase(confirm: bool) -> anyhow::Result<String> {
+    if credential_path(PASSPHRASE_CREDENTIAL).is_some_and(|path| path.exists()) {
+        return read_credential(PASSPHRASE_CREDENTIAL);
+    }
+    if let Ok(passphrase) = std::env::var(PASSPHRASE_ENV) {
+        return Ok(passphrase);
+    }
+    let passphrase = rpassword::prompt_password("Passphrase: ")?;
+    if confirm {
+        let again = rpassword::prompt_password("Passphrase again: ")?;
+        anyhow::ensure!(passphrase == again, "The passphrases differ");
+    }
+    anyhow::ensure!(!passphrase.is_empty(), "Empty passphrase");
+    Ok(passphrase)
+}
+
+fn key(passphrase: &str, salt: &[u8]) -> anyhow::Result<Key> {
+    let mut key = Key::default();
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| anyhow::anyhow!("Failed to derive a key: {}", e))?;
+    Ok(key)
+}
+
+/// `secret` encrypted with a key derived from `passphrase` by Argon2id,
+/// ready for the accounts file.
+pub fn encrypt(secret: &str, passphrase: &str) -> anyhow::Result<String> {
+    let mut salt = [0; SALT_LEN];
+    OsRng.fill_bytes(&mut salt);
+    let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let ciphertext = ChaCha20Poly1305::new(&key(passphrase, &salt)?)
+        .encrypt(&nonce, secret.as_bytes())
+        .map_err(|_| anyhow::anyhow!("Failed to encrypt"))?;
+
+    let mut sealed = salt.to_vec();
+    sealed.extend_from_slice(&nonce);
+    sealed.extend(ciphertext);
+    Ok(format!("{}{}", ENCRYPTED, BASE64.encode(sealed)))
+}
+
+fn decrypt(encrypted: &str, passphrase: &str) -> anyhow::Result<String> {
+    let sealed = BASE64
+        .decode(encrypted.trim())
+        .context("Encrypted secret isn't base64")?;
+    anyhow::ensure!(
+        sealed.len() > SALT_LEN + NONCE_LEN,
+        "Encrypted secret is too short"
+    );
+    let (salt, rest) = sealed.split_at(SALT_LEN);
+    let (nonce, ciphertext) = rest.split_at(NONCE_LEN);
+    let secret = ChaCha20Poly1305::new(&key(passphrase, salt)?)
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| anyhow::anyhow!("Wrong passphrase or damaged secret"))?;
+    String::from_utf8(secret).context("Decrypted secret isn't text")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A file of its own under the temporary directory, with `mode`.
+    fn file(name: &str, mode: u32) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("tfa-{}-{}", std::process::id(), name));
+        std::fs::write(&path, "JBSWY3DPEHPK3PXP\n").unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(mode)).unwrap();
+        path
+    }
+
+    fn sealed(secret: &str, passphrase: &str) -> String {
+        let encrypted = encrypt(secret, passphrase).unwrap();
+        assert!(is_encrypted(&encrypted));
+        encrypted.strip_prefix(ENCRYPTED).unwrap().to_string()
+    }
+
+    #[test]
+    fn decrypts_what_it_encrypted() {
+        let sealed = sealed("JBSWY3DPEHPK3PXP", "correct horse");
+        assert_eq!(
+            decrypt(&sealed, "correct horse").unwrap(),
+            "JBSWY3DPEHPK3PXP"
+        );
+    }
+
+    #[test]
+    fn refuses_the_wrong_passphrase() {
+        let sealed = sealed("JBSWY3DPEHPK3PXP", "correct horse");
+        let error = decrypt(&sealed, "battery staple").unwrap_err();
+        assert!(error.to_string().contains("Wrong passphrase"), "{}", error);
+    }
+
+    #[test]
+    fn refuses_truncated_secrets() {
+        let sealed = BASE64
+            .decode(sealed("JBSWY3DPEHPK3PXP", "correct horse"))
+            .unwrap();
+
+        let short = BASE64.encode(&sealed[..SALT_LEN + NONCE_LEN]);
+        let error = decrypt(&short, "correct horse").unwrap_err();
+        assert!(error.to_string().contains("too short"), "{}", error);
+
+        let cut = BASE64.encode(&sealed[..sealed.len() - 1]);
+        let error = decrypt(&cut, "correct horse").unwrap_err();
+        assert!(error.to_string().contains("damaged"), "{}", error);
+
+        assert!(decrypt("not base64!", "correct horse").is_err());
+    }
+
+    #[test]
+    fn reads_files_only_their_owner_can_read() {
+        let private = file("private", 0o600);
+        assert_eq!(read_file(&private).unwrap(), "JBSWY3DPEHPK3PXP");
+
+        let readable = file("readable", 0o644);
+        let error = check_permissions(&readable).unwrap_err();
+        assert!(error.to_string().contains("mode 644"), "{}", error);
+        assert!(read_file(&readable).is_err());
+
+        std::fs::remove_file(private).unwrap();
+        std::fs::remove_file(readable).unwrap();
+    }
+}
@@ -0,0 +1,122 @@
+//! Serving over TLS and only to clients with a certificate signed by our
+//! CA, so the server needn't hide in a VPN.
+
+use anyhow::Context;
+use rustls::server::AllowAnyAuthenticatedClient;
+use rustls::{Certificate, PrivateKey, RootCertStore, ServerConfig};
+use std::path::Path;
+use std::sync::Arc;
+
+/// The server's `cert` chain and `key`, accepting clients signed by a CA
+/// in `client_ca`. All PEM files.
+pub fn config(cert: &Path, key: &Path, client_ca: &Path) -> anyhow::Result<Arc<ServerConfig>> {
+    let certs = read_certs(cert)?;
+    let key = read_key(key)?;
+
+    let mut roots = RootCertStore::empty();
+    for ca in read_certs(client_ca)? {
+        roots
+            .add(&ca)
+            .with_context(|| format!("Invalid CA certificate in {:?}", client_ca))?;
+    }
+
+    let config = ServerConfig::builder()
+        .with_safe_defaults()
+        .with_client_cert_verifier(AllowAnyAuthenticatedClient::new(roots).boxed())
+        .with_single_cert(certs, key)
+        .context("Invalid server certificate or key")?;
+    Ok(Arc::new(config))
+}
+
+fn open(path: &Path) -> anyhow::Result<std::io::BufReader<std::fs::File>> {
+    let file = std::fs::File::open(path).with_context(|| format!("Failed to open {:?}", path))?;
+    Ok(std::io::BufReader::new(file))
+}
+
+fn read_certs(path: &Path) -> anyhow::Result<Vec<Certificate>> {
+    let certs = rustls_pemfile::certs(&mut open(path)?)
+        .with_context(|| format!("Failed to read certificates from {:?}", path))?;
+    anyhow::ensure!(!certs.is_empty(), "No certificates in {:?}", path);
+    Ok(certs.into_iter().map(Certificate).collect())
+}
+
+/// The first PKCS#8, RSA or EC private key in the file.
+fn read_key(path: &Path) -> anyhow::Result<PrivateKey> {
+    let mut reader = open(path)?;
+    loop {
+        match rustls_pemfile::read_one(&mut reader)
+            .with_context(|| format!("Failed to read a key from {:?}", path))?
+        {
+            Some(rustls_pemfile::Item::PKCS8Key(key))
+            | Some(rustls_pemfile::Item::RSAKey(key))
+            | Some(rustls_pemfile::Item::ECKey(key)) => return Ok(PrivateKey(key)),
+            Some(_) => continue,
+            None => anyhow::bail!("No private key in {:?}", path),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    /// A CA and a `localhost` certificate signed by it, made with openssl:
+    /// `ca.pem`, `server.pem` with its key as `server.key` (PKCS#8) and
+    /// `server-ec.key` (SEC1).
+    fn testdata(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("testdata")
+            .join(name)
+    }
+
+    #[test]
+    fn loads_the_certificate_key_and_client_ca() {
+        config(
+            &testdata("server.pem"),
+            &testdata("server.key"),
+            &testdata("ca.pem"),
+        )
+        .unwrap();
+        config(
+            &testdata("server.pem"),
+            &testdata("server-ec.key"),
+            &testdata("ca.pem"),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn finds_the_key_after_a_certificate() {
+        let path = std::env::temp_dir().join(format!("tfa-{}-combined.pem", std::process::id()));
+        let mut pem = std::fs::read(testdata("server.pem")).unwrap();
+        pem.extend(std::fs::read(testdata("server.key")).unwrap());
+        std::fs::write(&path, pem).unwrap();
+
+        let key = read_key(&path).unwrap();
+        assert_eq!(key, read_key(&testdata("server.key")).unwrap());
+        std::fs::remove_file(path).unwrap();
+
+        let error = read_key(&testdata("server.pem")).unwrap_err();
+        assert!(error.to_string().contains("No private key"), "{}", error);
+    }
+
+    #[test]
+    fn refuses_a_missing_or_empty_client_ca() {
+        let error = config(
+            &testdata("server.pem"),
+            &testdata("server.key"),
+            &testdata("missing.pem"),
+        )
+        .unwrap_err();
+        assert!(error.to_string().contains("missing.pem"), "{}", error);
+
+        let error = config(
+            &testdata("server.pem"),
+            &testdata("server.key"),
+            &testdata("server.key"),
+        )
+        .unwrap_err();
+        assert!(error.to_string().contains("No certificates"), "{}", error);
+    }
+}